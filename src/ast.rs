@@ -5,6 +5,7 @@ macro_rules! ast {
         #[derive(Debug, Clone, PartialEq, Eq,Hash)]
         pub struct $ast_expr {
             $(pub $field: $type,)+
+            pub span: $crate::scanner::Span,
         })+
         #[derive(Debug, Clone, PartialEq, Eq,Hash)]
         pub enum $name {
@@ -28,6 +29,13 @@ macro_rules! ast {
                 )+
             }
         }
+        pub fn span(&self) -> $crate::scanner::Span {
+            match self {
+                $(
+                $name::$ast_expr(inner) => inner.span,
+                )+
+            }
+        }
         }
 
         pub trait Visit<R> {
@@ -0,0 +1,78 @@
+use std::fmt::Write;
+
+use crate::scanner::Span;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// An error rendered rustc style: header, location, the source line with the span underlined and notes
+pub(crate) struct Diagnostic<'a> {
+    pub message: &'a str,
+    pub span: Span,
+    /// Printed after the carets, may be empty
+    pub label: &'a str,
+    pub notes: &'a [String],
+}
+
+impl Diagnostic<'_> {
+    /// Render against `source`, the code `span` comes from, the snippet is left out if it is missing
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let paint = |style| if color { style } else { "" };
+        let (red, blue, bold, reset) = (paint(RED), paint(BLUE), paint(BOLD), paint(RESET));
+        let line_number = self.span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{red}error{reset}{bold}: {}{reset}", self.message);
+        let _ = writeln!(
+            out,
+            "{gutter}{blue}-->{reset} line {}:{}",
+            self.span.line, self.span.column
+        );
+        if let Some((text, column)) = self.snippet(source) {
+            // keep tabs so the carets line up with the source line
+            let padding: String = text
+                .chars()
+                .take(column)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let width = source
+                .get(self.span.start..self.span.end)
+                .unwrap_or_default()
+                .chars()
+                .take_while(|&c| c != '\n')
+                .count()
+                .max(1);
+            let underline = format!("{}{}", "^".repeat(width), self.label_suffix());
+            let _ = writeln!(out, "{gutter} {blue}|{reset}");
+            let _ = writeln!(out, "{blue}{line_number} |{reset} {text}");
+            let _ = writeln!(
+                out,
+                "{gutter} {blue}|{reset} {padding}{red}{underline}{reset}"
+            );
+        }
+        for note in self.notes {
+            let _ = writeln!(out, "{gutter} {blue}={reset} {bold}note{reset}: {note}");
+        }
+        out
+    }
+
+    fn label_suffix(&self) -> String {
+        if self.label.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", self.label)
+        }
+    }
+
+    /// The line the span starts on and the span's column as a char index into it
+    fn snippet<'s>(&self, source: &'s str) -> Option<(&'s str, usize)> {
+        let before = source.get(..self.span.start)?;
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        let column = before[line_start..].chars().count();
+        let text = source[line_start..].lines().next().unwrap_or_default();
+        Some((text, column))
+    }
+}
@@ -0,0 +1,96 @@
+use std::{fmt, io};
+
+use crate::diagnostic::Diagnostic;
+use crate::interpreter::{Abort, RuntimeError};
+use crate::scanner::{Span, Token, TokenType};
+
+/// A diagnostic reported by the scanner, the parser or the resolver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub span: Span,
+    /// Either empty, " at end" or " at '<lexeme>'"
+    pub location: String,
+    pub message: String,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(span: Span, message: impl fmt::Display) -> Self {
+        Self {
+            span,
+            location: String::new(),
+            message: message.to_string(),
+        }
+    }
+    pub(crate) fn at(token: &Token, message: impl fmt::Display) -> Self {
+        let location = if token.ttype == TokenType::EOF {
+            " at end".to_string()
+        } else {
+            format!(" at '{}'", token.lexeme)
+        };
+        Self {
+            span: token.span(),
+            location,
+            message: message.to_string(),
+        }
+    }
+    pub(crate) fn diagnostic(&self) -> Diagnostic<'_> {
+        Diagnostic {
+            message: &self.message,
+            span: self.span,
+            label: self.location.trim_start(),
+            notes: &[],
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Error{}: {}",
+            self.span.line, self.span.column, self.location, self.message
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum LoxError {
+    Scan(Vec<SyntaxError>),
+    Parse(Vec<SyntaxError>),
+    Resolve(Vec<SyntaxError>),
+    Runtime(Box<RuntimeError>),
+    /// A host call through `Lox::call` that could not be made, or a native that failed
+    Call(String),
+    /// The host stopped the script, it may have partially run
+    Aborted(Abort),
+    Io(io::Error),
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Scan(errors) | LoxError::Parse(errors) | LoxError::Resolve(errors) => {
+                for (idx, error) in errors.iter().enumerate() {
+                    if idx > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+            LoxError::Runtime(error) => write!(f, "{}", error),
+            LoxError::Call(message) => write!(f, "{}", message),
+            LoxError::Aborted(Abort::Interrupted) => write!(f, "Interrupted."),
+            LoxError::Aborted(Abort::BudgetExhausted) => write!(f, "Step budget exhausted."),
+            LoxError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+impl From<io::Error> for LoxError {
+    fn from(error: io::Error) -> Self {
+        LoxError::Io(error)
+    }
+}
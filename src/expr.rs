@@ -1,4 +1,4 @@
-use crate::{interpreter::Object, scanner::Token};
+use crate::{interpreter::Object, scanner::Token, stmt};
 
 crate::ast!(
 -Expr-
@@ -9,12 +9,30 @@ Call => visit_call_expr =>  callee Box<Expr> paren Token  arguemnts Vec<Expr>,
 
 Get => visit_get_expr =>  object Box<Expr> name Token,
 
+Index => visit_index_expr => object Box<Expr> bracket Token index Box<Expr>,
+
+IndexSet => visit_index_set_expr => object Box<Expr> bracket Token index Box<Expr> value Box<Expr>,
+
+IndexSetOp => visit_index_set_op_expr => object Box<Expr> bracket Token index Box<Expr> operator Token value Box<Expr> postfix bool,
+
+List => visit_list_expr => elements Vec<Expr>,
+
+Map => visit_map_expr => brace Token entries Vec<(Expr, Expr)>,
+
 Assign => visit_assign_expr => name Token value Box<Expr>,
 
+AssignOp => visit_assign_op_expr => name Token operator Token value Box<Expr> postfix bool,
+
 Grouping => visit_grouping_expr => expression Box<Expr>,
 
 Literal => visit_literal_expr => value Object,
 
+Interpolation => visit_interpolation_expr => parts Vec<Expr>,
+
+Lambda => visit_lambda_expr => function stmt::Function,
+
+Conditional => visit_conditional_expr => condition Box<Expr> then_branch Box<Expr> else_branch Box<Expr>,
+
 Logical => visit_logical_expr => left Box<Expr> operator Token right Box<Expr>,
 
 Unary => visit_unary_expr => operator Token right Box<Expr>,
@@ -25,5 +43,7 @@ This => visit_this_expr => keyword Token,
 
 Set => visit_set_expr => object Box<Expr> name Token value Box<Expr>,
 
+SetOp => visit_set_op_expr => object Box<Expr> name Token operator Token value Box<Expr> postfix bool,
+
 Variable => visit_variable_expr => name Token,
 );
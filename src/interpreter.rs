@@ -1,25 +1,55 @@
-use crate::interpreter::object::function::{Clock, LoxFunction};
-use crate::scanner::Token;
+use crate::diagnostic::Diagnostic;
+use crate::interpreter::object::function::{self, LoxFunction, NativeFunction};
+use crate::scanner::{Span, Token};
 use crate::{ar, downcast, null_obj};
-use crate::{expr, obj, scanner::TokenType, stmt};
-use std::collections::HashMap;
+use crate::{expr, obj, scanner::TokenType, stmt, Writer};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
 mod environment;
 use environment::Environment;
 mod object;
-pub use object::{class::LoxClass, Object, ObjectInner};
-
-use trycatch::{throw, Exception};
-
-#[derive(Clone, Debug)]
+pub use object::{
+    class::LoxClass,
+    convert::{FromLox, FromLoxError, IntoLox},
+    instance::LoxInstance,
+    list::LoxList,
+    lox_callable::LoxCallable,
+    lox_trait::LoxTrait,
+    map::LoxMap,
+    Object, ObjectInner,
+};
+
+use trycatch::{catch, throw, CatchError, Exception, ExceptionDowncast};
+
+#[derive(Clone)]
 pub struct Interpreter {
     environment: Arc<RwLock<Environment>>,
     globals: Arc<RwLock<Environment>>,
-    pub locals: Arc<RwLock<HashMap<expr::Expr, usize>>>,
+    pub(crate) locals: Arc<RwLock<HashMap<expr::Expr, usize>>>,
+    // Spans of the loops with a break or continue, only those pay for catching the jump
+    jump_loops: Arc<RwLock<HashSet<Span>>>,
+    output: Writer,
+    // Shared between clones, so counting and interrupting works inside function calls too
+    steps: Arc<AtomicUsize>,
+    step_budget: Option<usize>,
+    interrupt: Arc<AtomicBool>,
+    // Every call runs on its own clone, so this needs no sharing
+    pub(crate) call_depth: usize,
+    pub(crate) max_call_depth: usize,
+    // Calls in progress, a runtime error unwinding through them leaves them in place
+    frames: Arc<Mutex<Vec<Frame>>>,
 }
 
+/// Deep enough for ordinary recursion while fitting an 8 MiB stack, the size of a Linux main thread
+///
+/// A call takes about 9 KB of native stack in debug builds, up to 15 KB with loops and blocks in
+/// its body, and roughly a third of that optimized
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 255;
+
 impl stmt::Visit<()> for Interpreter {
     fn visit_expression_stmt(&mut self, stmt: &stmt::Expression) {
         self.evaluate(&stmt.expression);
@@ -27,7 +57,7 @@ impl stmt::Visit<()> for Interpreter {
 
     fn visit_print_stmt(&mut self, stmt: &stmt::Print) {
         let value = self.evaluate(&stmt.expression);
-        println!("{}", stringify(value));
+        let _ = writeln!(self.output.lock().unwrap(), "{}", stringify(value));
     }
 
     fn visit_var_stmt(&mut self, stmt: &stmt::Var) {
@@ -58,13 +88,48 @@ impl stmt::Visit<()> for Interpreter {
     }
 
     fn visit_while_stmt(&mut self, stmt: &stmt::While) {
+        if !self.jump_loops.try_read().unwrap().contains(&stmt.span) {
+            while is_truthy(&self.evaluate(&stmt.condition)) {
+                self.execute(&stmt.body);
+                if let Some(ref increment) = stmt.increment {
+                    self.evaluate(increment);
+                }
+            }
+            return;
+        }
         while is_truthy(&self.evaluate(&stmt.condition)) {
-            self.execute(&stmt.body)
+            // run the body on a clone, a jump out of a nested block must not leave its environment behind
+            let mut interpreter = self.clone();
+            let body = &stmt.body;
+            if let Err(e) = catch(move || interpreter.execute(body)) {
+                match e {
+                    CatchError::Exception(e) => match e.try_downcast::<BreakException>() {
+                        Ok(_) => break,
+                        Err(exception) => {
+                            if let Err(exception) = exception.downcast::<ContinueException>() {
+                                rethrow(exception)
+                            }
+                        }
+                    },
+                    CatchError::Panic(p) => std::panic::panic_any(p),
+                }
+            }
+            if let Some(ref increment) = stmt.increment {
+                self.evaluate(increment);
+            }
         }
     }
 
+    fn visit_break_stmt(&mut self, _stmt: &stmt::Break) {
+        throw(BreakException)
+    }
+
+    fn visit_continue_stmt(&mut self, _stmt: &stmt::Continue) {
+        throw(ContinueException)
+    }
+
     fn visit_function_stmt(&mut self, stmt: &stmt::Function) {
-        let function = LoxFunction::new(stmt.clone(), self.environment.clone(), false);
+        let function = LoxFunction::new(stmt.clone(), self.environment.clone(), false, None);
         self.environment.try_write().unwrap().define(
             stmt.name.lexeme.clone(),
             Some(obj!(function; @rr ObjectInner::Function)),
@@ -94,6 +159,41 @@ impl stmt::Visit<()> for Interpreter {
             None
         };
 
+        let mut traits = vec![];
+        for variable in &stmt.traits {
+            match self.evaluate(&variable.clone().into()).0 {
+                ObjectInner::Trait(lox_trait) => traits.push(lox_trait),
+                _ => throw(RuntimeError::new(
+                    variable.name.clone(),
+                    "Only traits can be used with 'with'.",
+                )),
+            }
+        }
+        // a method two traits define is ambiguous unless the class defines it too
+        let mut provided: HashMap<&String, &str> = HashMap::new();
+        for (variable, lox_trait) in stmt.traits.iter().zip(&traits) {
+            for method in lox_trait.method_names() {
+                if stmt.methods.iter().any(|m| &m.name.lexeme == method) {
+                    continue;
+                }
+                if let Some(other) = provided.insert(method, &lox_trait.name) {
+                    throw(
+                        RuntimeError::new(
+                            variable.name.clone(),
+                            format!(
+                                "Method '{}' is defined by both '{}' and '{}'.",
+                                method, other, lox_trait.name
+                            ),
+                        )
+                        .with_note(format!(
+                            "define '{}' in '{}' to choose between them",
+                            method, stmt.name.lexeme
+                        )),
+                    )
+                }
+            }
+        }
+
         self.environment
             .try_write()
             .unwrap()
@@ -111,14 +211,27 @@ impl stmt::Visit<()> for Interpreter {
                 method.clone(),
                 self.environment.clone(),
                 method.name.lexeme == "init",
+                Some(stmt.name.lexeme.clone()),
             );
             methods.insert(method.name.lexeme.clone(), function);
         }
+        let mut class_methods = HashMap::new();
+        for method in &stmt.class_methods {
+            let function = LoxFunction::new(
+                method.clone(),
+                self.environment.clone(),
+                false,
+                Some(stmt.name.lexeme.clone()),
+            );
+            class_methods.insert(method.name.lexeme.clone(), function);
+        }
 
         let class = ar!(ObjectInner::Class(LoxClass::new(
             stmt.name.lexeme.clone(),
             superclass.map(|class| downcast!(class => ObjectInner::Class)),
+            traits,
             methods,
+            class_methods,
         )));
         if stmt.superclass.is_some() {
             self.environment = self
@@ -136,6 +249,24 @@ impl stmt::Visit<()> for Interpreter {
             .unwrap()
             .assign(stmt.name.clone(), class);
     }
+
+    fn visit_trait_stmt(&mut self, stmt: &stmt::Trait) {
+        let mut methods = HashMap::new();
+        for method in &stmt.methods {
+            let function = LoxFunction::new(
+                method.clone(),
+                self.environment.clone(),
+                method.name.lexeme == "init",
+                Some(stmt.name.lexeme.clone()),
+            );
+            methods.insert(method.name.lexeme.clone(), function);
+        }
+        let lox_trait = LoxTrait::new(stmt.name.lexeme.clone(), methods);
+        self.environment.try_write().unwrap().define(
+            stmt.name.lexeme.clone(),
+            Some(ar!(ObjectInner::Trait(lox_trait))),
+        );
+    }
 }
 
 impl expr::Visit<Object> for Interpreter {
@@ -143,51 +274,7 @@ impl expr::Visit<Object> for Interpreter {
         let right = self.evaluate(&expr.right);
         let left = self.evaluate(&expr.left);
 
-        match expr.operator.ttype {
-            TokenType::MINUS => {
-                check_number_operands(&expr.operator, [&left, &right]);
-                return obj!(downcast!(left => ObjectInner::Number) - downcast!(right => ObjectInner::Number) ; ObjectInner::Number);
-            }
-            TokenType::PLUS => {
-                if left.is_num() && right.is_num() {
-                    return obj!(downcast!(left => ObjectInner::Number) + downcast!(right => ObjectInner::Number) ; ObjectInner::Number);
-                }
-                if left.is_str() && right.is_str() {
-                    return obj!(downcast!(left => ObjectInner::String) + &downcast!(right => ObjectInner::String) ; ObjectInner::String);
-                }
-                throw(RuntimeError::new(
-                    expr.operator.clone(),
-                    "Operands must be two numbers or two strings.",
-                ))
-            }
-            TokenType::SLASH => {
-                check_number_operands(&expr.operator, [&left, &right]);
-                return obj!(downcast!(left => ObjectInner::Number) / downcast!(right => ObjectInner::Number) ; ObjectInner::Number);
-            }
-            TokenType::STAR => {
-                check_number_operands(&expr.operator, [&left, &right]);
-                return obj!(downcast!(left => ObjectInner::Number) * downcast!(right => ObjectInner::Number) ; ObjectInner::Number);
-            }
-            TokenType::GREATER => {
-                check_number_operands(&expr.operator, [&left, &right]);
-                return obj!(downcast!(left => ObjectInner::Number) > downcast!(right => ObjectInner::Number) ; ObjectInner::Bool);
-            }
-            TokenType::GREATER_EQUAL => {
-                check_number_operands(&expr.operator, [&left, &right]);
-                return obj!(downcast!(left => ObjectInner::Number) >= downcast!(right => ObjectInner::Number) ; ObjectInner::Bool);
-            }
-            TokenType::LESS => {
-                check_number_operands(&expr.operator, [&left, &right]);
-                return obj!(downcast!(left => ObjectInner::Number) < downcast!(right => ObjectInner::Number) ; ObjectInner::Bool);
-            }
-            TokenType::LESS_EQUAL => {
-                check_number_operands(&expr.operator, [&left, &right]);
-                return obj!(downcast!(left => ObjectInner::Number) <= downcast!(right => ObjectInner::Number) ; ObjectInner::Bool);
-            }
-            TokenType::BANG_EQUAL => return obj!(!is_equal(left, right) ; ObjectInner::Bool),
-            TokenType::EQUAL_EQUAL => return obj!(is_equal(left, right) ; ObjectInner::Bool),
-            _ => unreachable!(),
-        }
+        binary(&expr.operator, left, right)
     }
 
     fn visit_grouping_expr(&mut self, expr: &crate::expr::Grouping) -> Object {
@@ -206,6 +293,10 @@ impl expr::Visit<Object> for Interpreter {
                 return obj!(-downcast!(right =>ObjectInner::Number); ObjectInner::Number);
             }
             TokenType::BANG => return obj!(!is_truthy(&right); ObjectInner::Bool),
+            TokenType::TILDE => {
+                let [value] = check_integer_operands(&expr.operator, [&right]);
+                obj!(!value as f64; ObjectInner::Number)
+            }
 
             _ => unreachable!(),
         }
@@ -217,25 +308,21 @@ impl expr::Visit<Object> for Interpreter {
 
     fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Object {
         let value = self.evaluate(&expr.value);
-        let distance = self.locals.try_read();
+        self.assign_variable(&expr.name, &expr::Expr::Assign(expr.clone()), value.clone());
+        value
+    }
 
-        let distance = distance
-            .as_ref()
-            .unwrap()
-            .get(&expr::Expr::Assign(expr.clone()));
-        if let Some(distance) = distance {
-            self.environment.try_write().unwrap().assign_at(
-                distance,
-                expr.name.clone(),
-                value.clone(),
-            );
+    fn visit_assign_op_expr(&mut self, expr: &expr::AssignOp) -> Object {
+        let key = expr::Expr::AssignOp(expr.clone());
+        let old = self.lookup_variable(&expr.name, &key);
+        let value = self.evaluate(&expr.value);
+        let new = binary(&expr.operator, old.clone(), value);
+        self.assign_variable(&expr.name, &key, new.clone());
+        if expr.postfix {
+            old
         } else {
-            self.globals
-                .try_write()
-                .unwrap()
-                .assign(expr.name.clone(), value.clone());
+            new
         }
-        value
     }
 
     fn visit_logical_expr(&mut self, expr: &expr::Logical) -> Object {
@@ -279,13 +366,46 @@ impl expr::Visit<Object> for Interpreter {
             ))
         }
 
-        function.clone().try_read().unwrap().call(self, arguemnts)
+        let function = function.clone();
+        let function = function.try_read().unwrap();
+        self.call_function(&*function, arguemnts, &expr.paren)
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &expr::Interpolation) -> Object {
+        let mut value = String::new();
+        for part in &expr.parts {
+            value += &stringify(self.evaluate(part));
+        }
+        obj!(value; ObjectInner::String)
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &expr::Lambda) -> Object {
+        let function =
+            LoxFunction::new(expr.function.clone(), self.environment.clone(), false, None);
+        obj!(function; @rr ObjectInner::Function)
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &expr::Conditional) -> Object {
+        if is_truthy(&self.evaluate(&expr.condition)) {
+            self.evaluate(&expr.then_branch)
+        } else {
+            self.evaluate(&expr.else_branch)
+        }
     }
 
     fn visit_get_expr(&mut self, expr: &expr::Get) -> Object {
         let object = self.evaluate(&expr.object);
         if let ObjectInner::Instance(instance) = object.0 {
-            return instance.get(&expr.name);
+            return instance.get(&expr.name, self);
+        }
+        if let ObjectInner::Class(class) = object.0 {
+            return class.get(&expr.name, self);
+        }
+        if let ObjectInner::List(list) = object.0 {
+            return list.get(&expr.name);
+        }
+        if let ObjectInner::Map(map) = object.0 {
+            return map.get(&expr.name);
         }
         throw(RuntimeError::new(
             expr.name.clone(),
@@ -306,14 +426,76 @@ impl expr::Visit<Object> for Interpreter {
         ))
     }
 
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Object {
+        let object = self.evaluate(&expr.object);
+        let index = self.evaluate(&expr.index);
+        at(&expr.bracket, get_index(&object, &index))
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &expr::IndexSet) -> Object {
+        let object = self.evaluate(&expr.object);
+        let index = self.evaluate(&expr.index);
+        let value = self.evaluate(&expr.value);
+        at(&expr.bracket, set_index(&object, &index, value.clone()));
+        value
+    }
+
+    fn visit_index_set_op_expr(&mut self, expr: &expr::IndexSetOp) -> Object {
+        let object = self.evaluate(&expr.object);
+        let index = self.evaluate(&expr.index);
+        let old = at(&expr.bracket, get_index(&object, &index));
+        let value = self.evaluate(&expr.value);
+        let new = binary(&expr.operator, old.clone(), value);
+        at(&expr.bracket, set_index(&object, &index, new.clone()));
+        if expr.postfix {
+            old
+        } else {
+            new
+        }
+    }
+
+    fn visit_list_expr(&mut self, expr: &expr::List) -> Object {
+        let elements = expr
+            .elements
+            .iter()
+            .map(|element| self.evaluate(element))
+            .collect();
+        ar!(ObjectInner::List(LoxList::new(elements)))
+    }
+
+    fn visit_map_expr(&mut self, expr: &expr::Map) -> Object {
+        let entries = expr
+            .entries
+            .iter()
+            .map(|(key, value)| (self.evaluate(key), self.evaluate(value)))
+            .collect();
+        let map = at(&expr.brace, LoxMap::new(entries));
+        ar!(ObjectInner::Map(map))
+    }
+
+    fn visit_set_op_expr(&mut self, expr: &expr::SetOp) -> Object {
+        let object = self.evaluate(&expr.object);
+        if let ObjectInner::Instance(mut instance) = object.0 {
+            let old = instance.get(&expr.name, self);
+            let value = self.evaluate(&expr.value);
+            let new = binary(&expr.operator, old.clone(), value);
+            instance.set(expr.name.clone(), new.clone());
+            return if expr.postfix { old } else { new };
+        }
+        throw(RuntimeError::new(
+            expr.name.clone(),
+            "Only instances have fields.",
+        ))
+    }
+
     fn visit_this_expr(&mut self, expr: &expr::This) -> Object {
         self.lookup_variable(&expr.keyword, &expr::Expr::This(expr.clone()))
     }
 
     fn visit_super_expr(&mut self, expr: &expr::Super) -> Object {
-        let distance = self.locals.try_read();
-        let distance = distance
-            .as_ref()
+        let distance = *self
+            .locals
+            .try_read()
             .unwrap()
             .get(&expr.clone().into())
             .unwrap();
@@ -321,18 +503,21 @@ impl expr::Visit<Object> for Interpreter {
             .environment
             .try_read()
             .unwrap()
-            .get_at(distance, "super");
+            .get_at(&distance, "super");
         let object = self
             .environment
             .try_read()
             .unwrap()
-            .get_at(&(*distance - 1), "this");
-        if let Some(method) =
-            downcast!(superclass => ObjectInner::Class).find_method(&expr.method.lexeme)
-        {
-            ar!(ObjectInner::Function(Arc::new(RwLock::new(
-                method.bind(downcast!( object => ObjectInner::Instance)),
-            ))))
+            .get_at(&(distance - 1), "this");
+        let superclass = downcast!(superclass => ObjectInner::Class);
+        // in a class method `this` is the class itself
+        let method = if object.is_class() {
+            superclass.find_class_method(&expr.method.lexeme)
+        } else {
+            superclass.find_method(&expr.method.lexeme)
+        };
+        if let Some(method) = method {
+            method.bind(object).access(self, &expr.method)
         } else {
             throw(RuntimeError::new(
                 expr.method.clone(),
@@ -342,6 +527,110 @@ impl expr::Visit<Object> for Interpreter {
     }
 }
 
+fn get_index(object: &Object, index: &Object) -> Result<Object, String> {
+    match &object.0 {
+        ObjectInner::List(list) => list.get_index(index),
+        ObjectInner::Map(map) => map.get_index(index),
+        _ => Err("Only lists and maps can be indexed.".into()),
+    }
+}
+
+fn set_index(object: &Object, index: &Object, value: Object) -> Result<(), String> {
+    match &object.0 {
+        ObjectInner::List(list) => list.set_index(index, value),
+        ObjectInner::Map(map) => map.set_index(index, value),
+        _ => Err("Only lists and maps can be indexed.".into()),
+    }
+}
+
+/// Raise a failed list or map access as a runtime error at `token`
+fn at<T>(token: &Token, result: Result<T, String>) -> T {
+    result.unwrap_or_else(|message| throw(RuntimeError::new(token.clone(), message)))
+}
+
+/// Apply an arithmetic, comparison or equality operator
+fn binary(operator: &Token, left: Object, right: Object) -> Object {
+    match operator.ttype {
+        TokenType::MINUS => {
+            check_number_operands(operator, [&left, &right]);
+            return obj!(downcast!(left => ObjectInner::Number) - downcast!(right => ObjectInner::Number) ; ObjectInner::Number);
+        }
+        TokenType::PLUS => {
+            if left.is_num() && right.is_num() {
+                return obj!(downcast!(left => ObjectInner::Number) + downcast!(right => ObjectInner::Number) ; ObjectInner::Number);
+            }
+            if left.is_str() && right.is_str() {
+                return obj!(downcast!(left => ObjectInner::String) + &downcast!(right => ObjectInner::String) ; ObjectInner::String);
+            }
+            throw(
+                RuntimeError::new(
+                    operator.clone(),
+                    "Operands must be two numbers or two strings.",
+                )
+                .with_note(found(&[&left, &right])),
+            )
+        }
+        TokenType::SLASH => {
+            check_number_operands(operator, [&left, &right]);
+            return obj!(downcast!(left => ObjectInner::Number) / downcast!(right => ObjectInner::Number) ; ObjectInner::Number);
+        }
+        TokenType::STAR => {
+            check_number_operands(operator, [&left, &right]);
+            return obj!(downcast!(left => ObjectInner::Number) * downcast!(right => ObjectInner::Number) ; ObjectInner::Number);
+        }
+        TokenType::GREATER => {
+            check_number_operands(operator, [&left, &right]);
+            return obj!(downcast!(left => ObjectInner::Number) > downcast!(right => ObjectInner::Number) ; ObjectInner::Bool);
+        }
+        TokenType::GREATER_EQUAL => {
+            check_number_operands(operator, [&left, &right]);
+            return obj!(downcast!(left => ObjectInner::Number) >= downcast!(right => ObjectInner::Number) ; ObjectInner::Bool);
+        }
+        TokenType::LESS => {
+            check_number_operands(operator, [&left, &right]);
+            return obj!(downcast!(left => ObjectInner::Number) < downcast!(right => ObjectInner::Number) ; ObjectInner::Bool);
+        }
+        TokenType::LESS_EQUAL => {
+            check_number_operands(operator, [&left, &right]);
+            return obj!(downcast!(left => ObjectInner::Number) <= downcast!(right => ObjectInner::Number) ; ObjectInner::Bool);
+        }
+        TokenType::PERCENT => {
+            check_number_operands(operator, [&left, &right]);
+            obj!(downcast!(left => ObjectInner::Number) % downcast!(right => ObjectInner::Number) ; ObjectInner::Number)
+        }
+        TokenType::STAR_STAR => {
+            check_number_operands(operator, [&left, &right]);
+            obj!(downcast!(left => ObjectInner::Number).powf(downcast!(right => ObjectInner::Number)) ; ObjectInner::Number)
+        }
+        TokenType::AMPERSAND | TokenType::PIPE | TokenType::CARET => {
+            let [left, right] = check_integer_operands(operator, [&left, &right]);
+            let value = match operator.ttype {
+                TokenType::AMPERSAND => left & right,
+                TokenType::PIPE => left | right,
+                _ => left ^ right,
+            };
+            obj!(value as f64; ObjectInner::Number)
+        }
+        TokenType::LESS_LESS | TokenType::GREATER_GREATER => {
+            let [left, right] = check_integer_operands(operator, [&left, &right]);
+            if !(0..64).contains(&right) {
+                throw(RuntimeError::new(
+                    operator.clone(),
+                    "Shift amount must be between 0 and 63.",
+                ))
+            }
+            let value = if operator.ttype == TokenType::LESS_LESS {
+                left << right
+            } else {
+                left >> right
+            };
+            obj!(value as f64; ObjectInner::Number)
+        }
+        TokenType::BANG_EQUAL => return obj!(!is_equal(left, right) ; ObjectInner::Bool),
+        TokenType::EQUAL_EQUAL => return obj!(is_equal(left, right) ; ObjectInner::Bool),
+        _ => unreachable!(),
+    }
+}
 fn check_number_operands<'a>(
     operator: &crate::scanner::Token,
     operators: impl IntoIterator<Item = &'a Object>,
@@ -350,17 +639,36 @@ fn check_number_operands<'a>(
     if operators.iter().all(|obj| obj.is_num()) {
         return;
     }
-    if operators.len() > 1 {
-        throw(RuntimeError::new(
-            operator.clone(),
-            "Operands must be numbers.",
-        ));
+    let message = if operators.len() > 1 {
+        "Operands must be numbers."
     } else {
-        throw(RuntimeError::new(
-            operator.clone(),
-            "Operand must be a number.",
-        ));
-    }
+        "Operand must be a number."
+    };
+    throw(RuntimeError::new(operator.clone(), message).with_note(found(&operators)));
+}
+/// The values of operands to bitwise operators, which only work on integral numbers
+fn check_integer_operands<const N: usize>(operator: &Token, operands: [&Object; N]) -> [i64; N] {
+    check_number_operands(operator, operands);
+    operands.map(|operand| {
+        let value = downcast!(operand => ObjectInner::Number);
+        // i64::MAX as f64 rounds up to 2^63, so the upper bound is exclusive
+        if value.fract() != 0. || value < i64::MIN as f64 || value >= i64::MAX as f64 {
+            let message = if N > 1 {
+                "Operands must be integers."
+            } else {
+                "Operand must be an integer."
+            };
+            throw(
+                RuntimeError::new(operator.clone(), message)
+                    .with_note(format!("found {}", stringify(operand.clone()))),
+            )
+        }
+        value as i64
+    })
+}
+fn found(operands: &[&Object]) -> String {
+    let types: Vec<_> = operands.iter().map(|obj| obj.type_name()).collect();
+    format!("found {}", types.join(" and "))
 }
 fn is_equal(right: Object, left: Object) -> bool {
     right == left
@@ -373,40 +681,138 @@ fn is_truthy(right: &Object) -> bool {
     crate::try_downcast!(right => ObjectInner::Bool).unwrap_or(true)
 }
 
-impl Default for Interpreter {
-    fn default() -> Self {
+impl Interpreter {
+    pub fn new(output: Writer) -> Self {
         let globals = Arc::new(RwLock::new(Environment::new(None)));
         let environment = globals.clone();
 
-        globals.try_write().unwrap().define(
-            "clock".into(),
-            Some(obj!(Clock{}; @rr ObjectInner::Function)),
-        );
-
-        Self {
+        let mut interpreter = Self {
             globals,
             environment,
             locals: Default::default(),
+            jump_loops: Default::default(),
+            output,
+            steps: Default::default(),
+            step_budget: None,
+            interrupt: Default::default(),
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            frames: Default::default(),
+        };
+        interpreter.define_native("clock", 0, function::clock);
+        interpreter
+    }
+    pub(crate) fn get_global(&self, name: &str) -> Option<Object> {
+        self.globals.try_read().unwrap().get_by_name(name)
+    }
+    pub(crate) fn define_global(&mut self, name: &str, value: Object) {
+        self.globals
+            .try_write()
+            .unwrap()
+            .define(name.into(), Some(value));
+    }
+    pub(crate) fn callable(object: &Object) -> Option<Arc<RwLock<dyn LoxCallable>>> {
+        if object.is_fun() {
+            Some(crate::downcast_to_lox_callable!(object))
+        } else {
+            None
         }
     }
-}
-impl Interpreter {
+    pub(crate) fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(Vec<Object>) -> Result<Object, String> + Send + Sync + 'static,
+    ) {
+        self.globals.try_write().unwrap().define(
+            name.into(),
+            Some(
+                obj!(NativeFunction::new(name.into(), arity, function); @rr ObjectInner::Function),
+            ),
+        );
+    }
+    /// Call `function` in a new stack frame, an error it returns is raised at `token`
+    pub(crate) fn call_function(
+        &mut self,
+        function: &dyn LoxCallable,
+        arguments: Vec<Object>,
+        token: &Token,
+    ) -> Object {
+        match self.call_in_frame(function, arguments, Some(token.line)) {
+            Ok(value) => value,
+            Err(message) => throw(RuntimeError::new(token.clone(), message)),
+        }
+    }
+    /// Like `call_function` for a call without a call site, the error is returned
+    pub(crate) fn call_from_host(
+        &mut self,
+        function: &dyn LoxCallable,
+        arguments: Vec<Object>,
+    ) -> Result<Object, String> {
+        self.call_in_frame(function, arguments, None)
+    }
+    fn call_in_frame(
+        &mut self,
+        function: &dyn LoxCallable,
+        arguments: Vec<Object>,
+        line: Option<usize>,
+    ) -> Result<Object, String> {
+        self.frames.lock().unwrap().push(Frame {
+            function: function.name(),
+            line,
+        });
+        let result = function.call(self, arguments);
+        self.frames.lock().unwrap().pop();
+        result
+    }
     fn evaluate(&mut self, expression: &crate::expr::Expr) -> Object {
         expression.accept(self)
     }
-    pub fn interpret(&mut self, statements: Vec<crate::stmt::Stmt>) {
+    pub(crate) fn interpret(&mut self, statements: Vec<crate::stmt::Stmt>) {
         for stmt in statements {
             self.execute(&stmt);
         }
     }
 
     fn execute(&mut self, stmt: &crate::stmt::Stmt) {
+        self.safepoint();
         stmt.accept(self);
     }
 
+    /// Abort if the host interrupted us or the step budget is used up
+    fn safepoint(&self) {
+        if self.interrupt.swap(false, Ordering::Relaxed) {
+            throw(AbortException(Abort::Interrupted));
+        }
+        if let Some(budget) = self.step_budget {
+            if self.steps.fetch_add(1, Ordering::Relaxed) >= budget {
+                throw(AbortException(Abort::BudgetExhausted));
+            }
+        }
+    }
+    pub(crate) fn set_step_budget(&mut self, budget: Option<usize>) {
+        self.step_budget = budget;
+    }
+    pub(crate) fn reset_steps(&self) {
+        self.steps.store(0, Ordering::Relaxed);
+    }
+    /// The calls a runtime error escaped from, innermost first
+    pub(crate) fn take_frames(&self) -> Vec<Frame> {
+        let mut frames = std::mem::take(&mut *self.frames.lock().unwrap());
+        frames.reverse();
+        frames
+    }
+    pub(crate) fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(self.interrupt.clone())
+    }
+
     pub(crate) fn resolve(&mut self, expr: &expr::Expr, depth: usize) {
         self.locals.try_write().unwrap().insert(expr.clone(), depth);
     }
+    /// The loop at `span` contains a break or continue
+    pub(crate) fn resolve_jump(&mut self, span: Span) {
+        self.jump_loops.try_write().unwrap().insert(span);
+    }
 
     /// Execute a block using a new empty environment with our original environment as enclosing
     fn execute_block(&mut self, statements: &[stmt::Stmt], environment: Environment) {
@@ -418,6 +824,20 @@ impl Interpreter {
         self.environment = previous;
     }
 
+    fn assign_variable(&mut self, name: &Token, expr: &expr::Expr, value: Object) {
+        if let Some(distance) = self.locals.try_read().unwrap().get(expr) {
+            self.environment
+                .try_write()
+                .unwrap()
+                .assign_at(distance, name.clone(), value);
+        } else {
+            self.globals
+                .try_write()
+                .unwrap()
+                .assign(name.clone(), value);
+        }
+    }
+
     fn lookup_variable(&mut self, name: &crate::scanner::Token, expr: &expr::Expr) -> Object {
         if let Some(distance) = self.locals.try_read().unwrap().get(expr) {
             self.environment
@@ -442,6 +862,8 @@ fn stringify(obj: Object) -> String {
 pub struct RuntimeError {
     token: Token,
     message: String,
+    notes: Vec<String>,
+    pub(crate) trace: Vec<Frame>,
 }
 
 impl RuntimeError {
@@ -449,15 +871,109 @@ impl RuntimeError {
         Self {
             token,
             message: message.to_string(),
+            notes: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+    fn with_note(mut self, note: impl ToString) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    /// Extra hints shown under the error
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+    pub fn line(&self) -> usize {
+        self.token.line
+    }
+    pub fn span(&self) -> Span {
+        self.token.span()
+    }
+    /// The calls the error escaped from, innermost first
+    pub fn trace(&self) -> &[Frame] {
+        &self.trace
+    }
+    pub(crate) fn diagnostic(&self) -> Diagnostic<'_> {
+        Diagnostic {
+            message: &self.message,
+            span: self.span(),
+            label: "",
+            notes: &self.notes,
         }
     }
 }
 
 impl Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}\n[line {}]", self.message, self.token.line)
+        write!(
+            f,
+            "{}\n[line {}:{}]",
+            self.message, self.token.line, self.token.column
+        )
+    }
+}
+
+/// A call that was in progress when a runtime error was raised
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The function name, `Class.method` for methods
+    pub function: String,
+    /// Line of the call site, `None` for a call made by the host through `Lox::call`
+    pub line: Option<usize>,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}() called at line {}", self.function, line),
+            None => write!(f, "{}() called by the host", self.function),
+        }
     }
 }
 
 #[derive(Debug, Exception)]
 pub struct ReturnException(Object);
+
+#[derive(Debug, Exception)]
+pub struct BreakException;
+
+#[derive(Debug, Exception)]
+pub struct ContinueException;
+
+/// Throw again an exception that escaped a `catch`
+pub(crate) fn rethrow(exception: Box<dyn Any + Send>) -> ! {
+    match exception.downcast::<ReturnException>() {
+        Ok(ret) => throw(*ret),
+        Err(exception) => match exception.downcast::<RuntimeError>() {
+            Ok(error) => throw(*error),
+            Err(exception) => throw(*exception.downcast::<AbortException>().unwrap()),
+        },
+    }
+}
+
+/// Why the host stopped a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Abort {
+    /// `InterruptHandle::interrupt` was called
+    Interrupted,
+    /// The script executed more statements than `Lox::set_step_budget` allows
+    BudgetExhausted,
+}
+
+#[derive(Debug, Exception)]
+pub struct AbortException(pub Abort);
+
+/// Stops the running script from any thread, with `LoxError::Aborted(Abort::Interrupted)`
+///
+/// An interrupt raised while nothing runs stops the next script at its first statement
+#[derive(Debug, Clone)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
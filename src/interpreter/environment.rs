@@ -40,6 +40,10 @@ impl Environment {
             format!("Undefined variable '{}'.", token.lexeme,),
         ))
     }
+    /// Lookup in this environment only, without raising an error
+    pub fn get_by_name(&self, name: &str) -> Option<Object> {
+        self.values.try_read().unwrap().get(name).cloned().flatten()
+    }
     pub fn get_at(&self, distance: &usize, name: &str) -> Object {
         self.ancestor(distance)
             .try_read()
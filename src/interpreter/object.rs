@@ -1,4 +1,6 @@
 use std::{
+    cell::RefCell,
+    collections::HashSet,
     fmt,
     ops::Deref,
     sync::{Arc, RwLock},
@@ -6,12 +8,19 @@ use std::{
 
 use crate::uuid::Uuid;
 
-use self::{class::LoxClass, instance::LoxInstance, lox_callable::LoxCallable};
+use self::{
+    class::LoxClass, instance::LoxInstance, list::LoxList, lox_callable::LoxCallable,
+    lox_trait::LoxTrait, map::LoxMap,
+};
 
 pub mod class;
+pub mod convert;
 pub mod function;
-mod instance;
+pub mod instance;
+pub mod list;
 pub mod lox_callable;
+pub mod lox_trait;
+pub mod map;
 
 #[derive(Clone)]
 pub enum ObjectInner {
@@ -21,6 +30,9 @@ pub enum ObjectInner {
     Function(Arc<RwLock<dyn LoxCallable>>),
     Class(LoxClass),
     Instance(LoxInstance),
+    Trait(LoxTrait),
+    List(LoxList),
+    Map(LoxMap),
     Null,
 }
 #[derive(Clone)]
@@ -57,19 +69,37 @@ impl PartialEq for Object {
                 true
             }
             (ObjectInner::Class(c1), ObjectInner::Class(c2)) if c1.name == c2.name => true,
+            (ObjectInner::Trait(t1), ObjectInner::Trait(t2)) if t1.name == t2.name => true,
             (ObjectInner::Number(n1), ObjectInner::Number(n2)) if n1 == n2 => true,
             (ObjectInner::String(s1), ObjectInner::String(s2)) if s1 == s2 => true,
             (ObjectInner::Bool(b1), ObjectInner::Bool(b2)) if b1 == b2 => true,
             (ObjectInner::Null, ObjectInner::Null) => true,
             (ObjectInner::Function(l0), ObjectInner::Function(r0)) => Arc::ptr_eq(l0, r0),
+            (ObjectInner::List(l0), ObjectInner::List(r0)) => l0.ptr_eq(r0),
+            (ObjectInner::Map(l0), ObjectInner::Map(r0)) => l0.ptr_eq(r0),
             _ => false,
         }
     }
 }
 impl Eq for Object {}
+/// Consistent with `PartialEq`: by value, by class name or by identity for functions and containers
 impl std::hash::Hash for Object {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.1.hash(state)
+        std::mem::discriminant(&self.0).hash(state);
+        match &self.0 {
+            // 0 and -0 are equal
+            ObjectInner::Number(n) if *n == 0. => 0u64.hash(state),
+            ObjectInner::Number(n) => n.to_bits().hash(state),
+            ObjectInner::String(s) => s.hash(state),
+            ObjectInner::Bool(b) => b.hash(state),
+            ObjectInner::Class(c) => c.name.hash(state),
+            ObjectInner::Instance(i) => i.class.name.hash(state),
+            ObjectInner::Trait(t) => t.name.hash(state),
+            ObjectInner::Function(f) => (Arc::as_ptr(f) as *const ()).hash(state),
+            ObjectInner::List(l) => l.hash(state),
+            ObjectInner::Map(m) => m.hash(state),
+            ObjectInner::Null => (),
+        }
     }
 }
 impl fmt::Debug for Object {
@@ -82,6 +112,9 @@ impl fmt::Debug for Object {
             ObjectInner::Function(_) => f.debug_tuple("Function").finish(),
             ObjectInner::Class(c) => write!(f, "Class {}", c.to_string()),
             ObjectInner::Instance(i) => write!(f, "Instance {}", i.to_string()),
+            ObjectInner::Trait(t) => write!(f, "Trait {}", t),
+            ObjectInner::List(l) => write!(f, "List {}", l),
+            ObjectInner::Map(m) => write!(f, "Map {}", m),
             ObjectInner::Null => write!(f, "nil"),
         }
     }
@@ -98,10 +131,57 @@ impl fmt::Display for Object {
             ObjectInner::Null => write!(f, "nil"),
             ObjectInner::Class(c) => write!(f, "{}", c.to_string()),
             ObjectInner::Instance(i) => write!(f, "{}", i.to_string()),
+            ObjectInner::Trait(t) => write!(f, "{}", t),
+            ObjectInner::List(l) => write!(f, "{}", l),
+            ObjectInner::Map(m) => write!(f, "{}", m),
             ObjectInner::Function(lfn) => write!(f, "{}", lfn.try_read().unwrap()),
         }
     }
 }
+thread_local! {
+    // Containers whose Display is in progress on this thread
+    static PRINTING: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+/// Containers nested deeper than this are written as their placeholder
+const MAX_PRINT_DEPTH: usize = 64;
+
+/// Write the container at `ptr` with `write`, or `placeholder` if it is already being written
+///
+/// A list or map can contain itself, directly or through another container, or be nested deep
+/// enough to overflow the stack while writing
+pub(crate) fn write_once<T>(
+    ptr: *const T,
+    f: &mut fmt::Formatter<'_>,
+    placeholder: &str,
+    write: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+) -> fmt::Result {
+    let key = ptr as *const () as usize;
+    let entered = PRINTING.with(|printing| {
+        let mut printing = printing.borrow_mut();
+        printing.len() < MAX_PRINT_DEPTH && printing.insert(key)
+    });
+    if !entered {
+        return write!(f, "{}", placeholder);
+    }
+    let result = write(f);
+    PRINTING.with(|printing| printing.borrow_mut().remove(&key));
+    result
+}
+
+/// Drop `pending` and the containers only it holds one at a time instead of recursively
+///
+/// Dropping a deeply nested list or map would overflow the stack otherwise
+pub(crate) fn drop_nested(mut pending: Vec<Object>) {
+    while let Some(object) = pending.pop() {
+        match object.0 {
+            ObjectInner::List(mut list) => pending.extend(list.take_unique()),
+            ObjectInner::Map(mut map) => pending.extend(map.take_unique()),
+            _ => (),
+        }
+    }
+}
+
 impl Object {
     pub fn is_num(&self) -> bool {
         matches!(self.0, ObjectInner::Number(_))
@@ -119,6 +199,20 @@ impl Object {
     pub fn is_null(&self) -> bool {
         matches!(self.0, ObjectInner::Null)
     }
+    pub fn type_name(&self) -> &'static str {
+        match self.0 {
+            ObjectInner::Number(_) => "number",
+            ObjectInner::String(_) => "string",
+            ObjectInner::Bool(_) => "bool",
+            ObjectInner::Function(_) => "function",
+            ObjectInner::Class(_) => "class",
+            ObjectInner::Instance(_) => "instance",
+            ObjectInner::Trait(_) => "trait",
+            ObjectInner::List(_) => "list",
+            ObjectInner::Map(_) => "map",
+            ObjectInner::Null => "nil",
+        }
+    }
 }
 
 #[macro_export]
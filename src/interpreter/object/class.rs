@@ -1,41 +1,83 @@
 use std::collections::HashMap;
 
+use trycatch::throw;
+
 use crate::{
     ar,
-    interpreter::{Object, ObjectInner},
+    interpreter::{Interpreter, Object, ObjectInner, RuntimeError},
+    scanner::Token,
 };
 
-use super::{function::LoxFunction, instance::LoxInstance, lox_callable::LoxCallable};
+use super::{
+    function::LoxFunction, instance::LoxInstance, lox_callable::LoxCallable, lox_trait::LoxTrait,
+};
 
 #[derive(Debug, Clone)]
 pub struct LoxClass {
     pub name: String,
     methods: HashMap<String, LoxFunction>,
+    /// Methods called on the class itself, `this` is bound to the class
+    class_methods: HashMap<String, LoxFunction>,
     superclass: Option<Box<LoxClass>>,
+    /// Mixed in with `with`, after the class's own methods and before the superclass's
+    traits: Vec<LoxTrait>,
 }
 
 impl LoxClass {
-    pub fn new(
+    pub(crate) fn new(
         name: String,
         superclass: Option<LoxClass>,
+        traits: Vec<LoxTrait>,
         methods: HashMap<String, LoxFunction>,
+        class_methods: HashMap<String, LoxFunction>,
     ) -> Self {
         Self {
             name,
             methods,
+            class_methods,
             superclass: superclass.map(Box::new),
+            traits,
         }
     }
 
+    /// Looks in the class, then its traits, then up the superclass chain
+    ///
+    /// Traits can't define the same method unless the class overrides it, so their order doesn't matter
     pub(crate) fn find_method(&self, name: &str) -> Option<LoxFunction> {
         if let Some(method) = self.methods.get(name) {
             return Some(method.clone());
         }
+        if let Some(method) = self.traits.iter().find_map(|t| t.find_method(name)) {
+            return Some(method);
+        }
         if let Some(ref superclass) = self.superclass {
             return superclass.find_method(name);
         }
         None
     }
+
+    /// Class methods are inherited like instance methods, as if by a metaclass
+    pub(crate) fn find_class_method(&self, name: &str) -> Option<LoxFunction> {
+        if let Some(method) = self.class_methods.get(name) {
+            return Some(method.clone());
+        }
+        if let Some(ref superclass) = self.superclass {
+            return superclass.find_class_method(name);
+        }
+        None
+    }
+
+    pub(crate) fn get(&self, name: &Token, interpreter: &mut Interpreter) -> Object {
+        if let Some(method) = self.find_class_method(&name.lexeme) {
+            return method
+                .bind(ar!(ObjectInner::Class(self.clone())))
+                .access(interpreter, name);
+        }
+        throw(RuntimeError::new(
+            name.clone(),
+            format!("Undefined property '{}'.", name.lexeme),
+        ))
+    }
 }
 impl std::fmt::Display for LoxClass {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -55,14 +97,14 @@ impl LoxCallable for LoxClass {
         &self,
         interpreter: &mut crate::interpreter::Interpreter,
         arguemnts: Vec<Object>,
-    ) -> Object {
+    ) -> Result<Object, String> {
         let instance = LoxInstance::new(self.clone());
 
         if let Some(initializer) = self.find_method("init") {
             initializer
-                .bind(instance.clone())
-                .call(interpreter, arguemnts);
+                .bind(ar!(ObjectInner::Instance(instance.clone())))
+                .call(interpreter, arguemnts)?;
         }
-        ar!(ObjectInner::Instance(instance))
+        Ok(ar!(ObjectInner::Instance(instance)))
     }
 }
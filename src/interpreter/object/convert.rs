@@ -0,0 +1,177 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{null_obj, obj};
+
+use super::{list::LoxList, map::LoxMap, Object, ObjectInner};
+
+/// Conversion of a host value into a Lox value
+pub trait IntoLox {
+    fn into_lox(self) -> Object;
+}
+
+/// Conversion of a Lox value into a host value
+pub trait FromLox: Sized {
+    fn from_lox(object: Object) -> Result<Self, FromLoxError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FromLoxError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl FromLoxError {
+    fn new(expected: &'static str, found: &Object) -> Self {
+        Self {
+            expected,
+            found: found.type_name(),
+        }
+    }
+}
+
+impl fmt::Display for FromLoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Expected {} but got {}.", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for FromLoxError {}
+
+// Lets natives use `?` on conversions
+impl From<FromLoxError> for String {
+    fn from(error: FromLoxError) -> Self {
+        error.to_string()
+    }
+}
+
+impl IntoLox for Object {
+    fn into_lox(self) -> Object {
+        self
+    }
+}
+impl FromLox for Object {
+    fn from_lox(object: Object) -> Result<Self, FromLoxError> {
+        Ok(object)
+    }
+}
+
+impl IntoLox for f64 {
+    fn into_lox(self) -> Object {
+        obj!(self; ObjectInner::Number)
+    }
+}
+impl FromLox for f64 {
+    fn from_lox(object: Object) -> Result<Self, FromLoxError> {
+        crate::try_downcast!(object => ObjectInner::Number)
+            .ok_or_else(|| FromLoxError::new("number", &object))
+    }
+}
+
+impl IntoLox for bool {
+    fn into_lox(self) -> Object {
+        obj!(self; ObjectInner::Bool)
+    }
+}
+impl FromLox for bool {
+    fn from_lox(object: Object) -> Result<Self, FromLoxError> {
+        crate::try_downcast!(object => ObjectInner::Bool)
+            .ok_or_else(|| FromLoxError::new("bool", &object))
+    }
+}
+
+impl IntoLox for String {
+    fn into_lox(self) -> Object {
+        obj!(self; ObjectInner::String)
+    }
+}
+impl IntoLox for &str {
+    fn into_lox(self) -> Object {
+        self.to_string().into_lox()
+    }
+}
+impl FromLox for String {
+    fn from_lox(object: Object) -> Result<Self, FromLoxError> {
+        crate::try_downcast!(object.clone() => ObjectInner::String)
+            .ok_or_else(|| FromLoxError::new("string", &object))
+    }
+}
+
+impl IntoLox for () {
+    fn into_lox(self) -> Object {
+        null_obj!()
+    }
+}
+impl FromLox for () {
+    fn from_lox(object: Object) -> Result<Self, FromLoxError> {
+        if object.is_null() {
+            Ok(())
+        } else {
+            Err(FromLoxError::new("nil", &object))
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Vec<T> {
+    fn into_lox(self) -> Object {
+        let elements = self.into_iter().map(IntoLox::into_lox).collect();
+        crate::ar!(ObjectInner::List(LoxList::new(elements)))
+    }
+}
+impl<T: FromLox> FromLox for Vec<T> {
+    fn from_lox(object: Object) -> Result<Self, FromLoxError> {
+        crate::try_downcast!(object.clone() => ObjectInner::List)
+            .ok_or_else(|| FromLoxError::new("list", &object))?
+            .to_vec()
+            .into_iter()
+            .map(T::from_lox)
+            .collect()
+    }
+}
+
+/// Maps keyed by the host types that are valid Lox keys, `f64` can't key a `HashMap`
+macro_rules! map_conversions {
+    ($($key: ty),+) => {
+        $(
+        impl<V: IntoLox> IntoLox for HashMap<$key, V> {
+            fn into_lox(self) -> Object {
+                let entries = self
+                    .into_iter()
+                    .map(|(key, value)| (key.into_lox(), value.into_lox()))
+                    .collect();
+                let map = LoxMap::new(entries).expect("string and bool keys are valid");
+                crate::ar!(ObjectInner::Map(map))
+            }
+        }
+        impl<V: FromLox> FromLox for HashMap<$key, V> {
+            fn from_lox(object: Object) -> Result<Self, FromLoxError> {
+                crate::try_downcast!(object.clone() => ObjectInner::Map)
+                    .ok_or_else(|| FromLoxError::new("map", &object))?
+                    .to_vec()
+                    .into_iter()
+                    .map(|(key, value)| Ok((<$key>::from_lox(key)?, V::from_lox(value)?)))
+                    .collect()
+            }
+        }
+        )+
+    };
+}
+map_conversions!(String, bool);
+
+/// `None` is nil
+impl<T: IntoLox> IntoLox for Option<T> {
+    fn into_lox(self) -> Object {
+        match self {
+            Some(value) => value.into_lox(),
+            None => null_obj!(),
+        }
+    }
+}
+impl<T: FromLox> FromLox for Option<T> {
+    fn from_lox(object: Object) -> Result<Self, FromLoxError> {
+        if object.is_null() {
+            Ok(None)
+        } else {
+            T::from_lox(object).map(Some)
+        }
+    }
+}
@@ -4,21 +4,25 @@ use std::{
     time::SystemTime,
 };
 
-use trycatch::{catch, throw, CatchError, ExceptionDowncast};
+use trycatch::{catch, CatchError, ExceptionDowncast};
 
 use crate::{
     ar,
-    interpreter::{environment::Environment, Interpreter, ReturnException, RuntimeError},
-    null_obj, obj, stmt,
+    interpreter::{environment::Environment, rethrow, Interpreter, ReturnException},
+    null_obj,
+    scanner::Token,
+    stmt,
 };
 
-use super::{instance::LoxInstance, lox_callable::LoxCallable, Object, ObjectInner};
+use super::{convert::IntoLox, lox_callable::LoxCallable, Object, ObjectInner};
 
 #[derive(Debug, Clone)]
 pub struct LoxFunction {
     declaration: stmt::Function,
     closure: Arc<RwLock<Environment>>,
     is_initializer: bool,
+    /// Name of the class for methods
+    class: Option<String>,
 }
 impl fmt::Display for LoxFunction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -27,25 +31,36 @@ impl fmt::Display for LoxFunction {
 }
 
 impl LoxFunction {
-    pub fn new(
+    pub(crate) fn new(
         declaration: stmt::Function,
         closure: Arc<RwLock<Environment>>,
         is_initializer: bool,
+        class: Option<String>,
     ) -> Self {
         Self {
             declaration,
             closure,
             is_initializer,
+            class,
         }
     }
-    pub fn bind(&self, instance: LoxInstance) -> LoxFunction {
+    /// `this` is an instance, or the class itself for class methods
+    pub fn bind(&self, this: Object) -> LoxFunction {
         let mut environment = Environment::new(Some(self.closure.clone()));
-        environment.define("this".into(), Some(ar!(ObjectInner::Instance(instance))));
+        environment.define("this".into(), Some(this));
         LoxFunction {
             declaration: self.declaration.clone(),
             closure: Arc::new(RwLock::new(environment)),
             is_initializer: self.is_initializer,
+            class: self.class.clone(),
+        }
+    }
+    /// The value of accessing a bound method as a property: getters run, other methods are returned
+    pub(crate) fn access(self, interpreter: &mut Interpreter, name: &Token) -> Object {
+        if self.declaration.getter {
+            return interpreter.call_function(&self, vec![], name);
         }
+        ar!(ObjectInner::Function(Arc::new(RwLock::new(self))))
     }
 }
 
@@ -53,59 +68,103 @@ impl LoxCallable for LoxFunction {
     fn arity(&self) -> usize {
         self.declaration.params.len()
     }
+    fn name(&self) -> String {
+        match self.class {
+            Some(ref class) => format!("{}.{}", class, self.declaration.name.lexeme),
+            None => self.declaration.name.lexeme.clone(),
+        }
+    }
 
-    fn call(&self, interpreter: &mut Interpreter, arguemnts: Vec<Object>) -> Object {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguemnts: Vec<Object>,
+    ) -> Result<Object, String> {
         let mut environment = Environment::new(Some(self.closure.clone()));
         for (param, arg) in self.declaration.params.iter().zip(arguemnts.into_iter()) {
             environment.define(param.lexeme.clone(), Some(arg));
         }
 
+        if interpreter.call_depth >= interpreter.max_call_depth {
+            return Err("Stack overflow.".into());
+        }
+
         let body = &self.declaration.body;
         let mut interpreter = interpreter.clone();
+        interpreter.call_depth += 1;
 
         let execution_result = catch(move || interpreter.execute_block(body, environment));
 
         if let Err(e) = execution_result {
-            return match e {
+            return Ok(match e {
                 CatchError::Exception(e) => {
-                    if self.is_initializer {
-                        self.closure.try_read().unwrap().get_at(&0, "this")
-                    } else {
-                        // Exception can be either ReturnException or RuntimeError
-                        match e.try_downcast::<ReturnException>() {
-                            Ok(ret) => ret.0,
-                            Err(exception) => throw(*exception.downcast::<RuntimeError>().unwrap()),
+                    // Exception can be either ReturnException, RuntimeError or AbortException
+                    match e.try_downcast::<ReturnException>() {
+                        Ok(_) if self.is_initializer => {
+                            self.closure.try_read().unwrap().get_at(&0, "this")
                         }
+                        Ok(ret) => ret.0,
+                        Err(exception) => rethrow(exception),
                     }
                 }
                 CatchError::Panic(p) => std::panic::panic_any(p),
-            };
+            });
         }
         if self.is_initializer {
-            return self.closure.try_read().unwrap().get_at(&0, "this");
+            return Ok(self.closure.try_read().unwrap().get_at(&0, "this"));
         }
-        null_obj!()
+        Ok(null_obj!())
     }
 }
 
-pub struct Clock {}
-impl fmt::Display for Clock {
+type NativeFn = dyn Fn(Vec<Object>) -> Result<Object, String> + Send + Sync;
+
+/// A function implemented by the host
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+impl NativeFunction {
+    pub fn new(
+        name: String,
+        arity: usize,
+        function: impl Fn(Vec<Object>) -> Result<Object, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+impl fmt::Display for NativeFunction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "<native fn>")
     }
 }
-impl LoxCallable for Clock {
+impl LoxCallable for NativeFunction {
     fn arity(&self) -> usize {
-        0
+        self.arity
+    }
+    fn name(&self) -> String {
+        self.name.clone()
     }
 
-    fn call(&self, _interpreter: &mut Interpreter, _arguemnts: Vec<Object>) -> Object {
-        obj!(
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as f64
-                / 1000. ; ObjectInner::Number
-        )
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguemnts: Vec<Object>,
+    ) -> Result<Object, String> {
+        (self.function)(arguemnts)
     }
 }
+
+pub(crate) fn clock(_arguemnts: Vec<Object>) -> Result<Object, String> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as f64
+        / 1000.;
+    Ok(now.into_lox())
+}
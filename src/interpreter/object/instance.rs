@@ -7,7 +7,7 @@ use trycatch::throw;
 
 use crate::{
     ar,
-    interpreter::{ObjectInner, RuntimeError},
+    interpreter::{Interpreter, ObjectInner, RuntimeError},
 };
 
 use super::{class::LoxClass, Object};
@@ -19,21 +19,26 @@ pub struct LoxInstance {
 }
 
 impl LoxInstance {
-    pub fn new(class: LoxClass) -> Self {
+    pub(crate) fn new(class: LoxClass) -> Self {
         Self {
             class,
             fields: Default::default(),
         }
     }
-    pub fn get(&self, name: &crate::scanner::Token) -> Object {
+    /// A field, a bound method or the value of a getter
+    pub(crate) fn get(
+        &self,
+        name: &crate::scanner::Token,
+        interpreter: &mut Interpreter,
+    ) -> Object {
         if let Some(field) = self.fields.try_read().unwrap().get(&name.lexeme) {
             return field.clone();
         }
         let method = self.class.find_method(&name.lexeme);
         if let Some(method) = method {
-            return ar!(ObjectInner::Function(Arc::new(RwLock::new(
-                method.bind(self.clone())
-            ))));
+            return method
+                .bind(ar!(ObjectInner::Instance(self.clone())))
+                .access(interpreter, name);
         }
         throw(RuntimeError::new(
             name.clone(),
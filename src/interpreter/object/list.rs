@@ -0,0 +1,166 @@
+use std::sync::{Arc, RwLock};
+
+use trycatch::throw;
+
+use crate::{
+    ar,
+    interpreter::{ObjectInner, RuntimeError},
+    null_obj, obj,
+    scanner::Token,
+};
+
+use super::{convert::IntoLox, drop_nested, function::NativeFunction, write_once, Object};
+
+/// A growable array, clones share the same elements
+#[derive(Clone, Default)]
+pub struct LoxList {
+    elements: Arc<RwLock<Vec<Object>>>,
+}
+
+impl LoxList {
+    pub(crate) fn new(elements: Vec<Object>) -> Self {
+        Self {
+            elements: Arc::new(RwLock::new(elements)),
+        }
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.elements.try_read().unwrap().len()
+    }
+    /// A copy of the current elements
+    pub(crate) fn to_vec(&self) -> Vec<Object> {
+        self.elements.try_read().unwrap().clone()
+    }
+    pub(crate) fn ptr_eq(&self, other: &LoxList) -> bool {
+        Arc::ptr_eq(&self.elements, &other.elements)
+    }
+    /// The elements if this is the last clone, leaving the list empty
+    pub(super) fn take_unique(&mut self) -> Vec<Object> {
+        Arc::get_mut(&mut self.elements)
+            .map(|elements| std::mem::take(elements.get_mut().unwrap()))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn get_index(&self, index: &Object) -> Result<Object, String> {
+        let elements = self.elements.try_read().unwrap();
+        let index = check_index(index, elements.len())?;
+        Ok(elements[index].clone())
+    }
+    pub(crate) fn set_index(&self, index: &Object, value: Object) -> Result<(), String> {
+        let mut elements = self.elements.try_write().unwrap();
+        let index = check_index(index, elements.len())?;
+        elements[index] = value;
+        Ok(())
+    }
+
+    /// The method `name` bound to this list
+    pub(crate) fn get(&self, name: &Token) -> Object {
+        let list = self.clone();
+        let method = match name.lexeme.as_str() {
+            "len" => {
+                NativeFunction::new("len".into(), 0, move |_| Ok((list.len() as f64).into_lox()))
+            }
+            "push" => NativeFunction::new("push".into(), 1, move |mut arguments| {
+                list.elements.try_write().unwrap().push(arguments.remove(0));
+                Ok(null_obj!())
+            }),
+            "pop" => NativeFunction::new("pop".into(), 0, move |_| {
+                list.elements
+                    .try_write()
+                    .unwrap()
+                    .pop()
+                    .ok_or_else(|| "Can't pop from an empty list.".to_string())
+            }),
+            "insert" => NativeFunction::new("insert".into(), 2, move |mut arguments| {
+                let mut elements = list.elements.try_write().unwrap();
+                let index = check_position(&arguments[0], elements.len())?;
+                elements.insert(index, arguments.remove(1));
+                Ok(null_obj!())
+            }),
+            "remove" => NativeFunction::new("remove".into(), 1, move |arguments| {
+                let mut elements = list.elements.try_write().unwrap();
+                let index = check_index(&arguments[0], elements.len())?;
+                Ok(elements.remove(index))
+            }),
+            "slice" => NativeFunction::new("slice".into(), 2, move |arguments| {
+                let elements = list.elements.try_read().unwrap();
+                let start = check_position(&arguments[0], elements.len())?;
+                let end = check_position(&arguments[1], elements.len())?;
+                if start > end {
+                    return Err(format!("Slice start {} is after its end {}.", start, end));
+                }
+                Ok(ar!(ObjectInner::List(LoxList::new(
+                    elements[start..end].to_vec()
+                ))))
+            }),
+            _ => throw(RuntimeError::new(
+                name.clone(),
+                format!("Undefined property '{}'.", name.lexeme),
+            )),
+        };
+        obj!(method; @rr ObjectInner::Function)
+    }
+}
+
+/// By identity, like `ptr_eq`
+impl std::hash::Hash for LoxList {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.elements).hash(state)
+    }
+}
+
+impl Drop for LoxList {
+    fn drop(&mut self) {
+        drop_nested(self.take_unique());
+    }
+}
+
+impl std::fmt::Display for LoxList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_once(Arc::as_ptr(&self.elements), f, "[...]", |f| {
+            write!(f, "[")?;
+            for (idx, element) in self.elements.try_read().unwrap().iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", element)?;
+            }
+            write!(f, "]")
+        })
+    }
+}
+impl std::fmt::Debug for LoxList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxList {}", self)
+    }
+}
+
+/// `index` as the position of an element of a list of length `len`
+fn check_index(index: &Object, len: usize) -> Result<usize, String> {
+    match integer(index)? {
+        index if index >= 0. && index < len as f64 => Ok(index as usize),
+        index => Err(out_of_bounds(index, len)),
+    }
+}
+
+/// Like `check_index`, but also allowing the position right after the last element
+fn check_position(index: &Object, len: usize) -> Result<usize, String> {
+    match integer(index)? {
+        index if index >= 0. && index <= len as f64 => Ok(index as usize),
+        index => Err(out_of_bounds(index, len)),
+    }
+}
+
+fn integer(index: &Object) -> Result<f64, String> {
+    match index.0 {
+        ObjectInner::Number(value) if value.fract() == 0. => Ok(value),
+        ObjectInner::Number(_) => Err(format!("List index must be an integer, found {}.", index)),
+        _ => Err(format!(
+            "List index must be an integer, found {}.",
+            index.type_name()
+        )),
+    }
+}
+
+fn out_of_bounds(index: f64, len: usize) -> String {
+    format!("List index {} out of bounds for length {}.", index, len)
+}
@@ -6,5 +6,14 @@ use super::Object;
 
 pub trait LoxCallable: Send + Sync + fmt::Display {
     fn arity(&self) -> usize;
-    fn call(&self, _interpreter: &mut Interpreter, _arguemnts: Vec<Object>) -> Object;
+    /// How the callable appears in stack traces
+    fn name(&self) -> String {
+        self.to_string()
+    }
+    /// An `Err` is raised as a runtime error at the call site
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _arguemnts: Vec<Object>,
+    ) -> Result<Object, String>;
 }
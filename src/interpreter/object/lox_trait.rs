@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+use super::function::LoxFunction;
+
+/// A set of methods classes can mix in with `with`
+#[derive(Debug, Clone)]
+pub struct LoxTrait {
+    pub name: String,
+    methods: HashMap<String, LoxFunction>,
+}
+
+impl LoxTrait {
+    pub(crate) fn new(name: String, methods: HashMap<String, LoxFunction>) -> Self {
+        Self { name, methods }
+    }
+
+    pub(crate) fn find_method(&self, name: &str) -> Option<LoxFunction> {
+        self.methods.get(name).cloned()
+    }
+
+    pub(crate) fn method_names(&self) -> impl Iterator<Item = &String> {
+        self.methods.keys()
+    }
+}
+impl std::fmt::Display for LoxTrait {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use trycatch::throw;
+
+use crate::{
+    ar,
+    interpreter::{ObjectInner, RuntimeError},
+    obj,
+    scanner::Token,
+};
+
+use super::{
+    convert::IntoLox, drop_nested, function::NativeFunction, list::LoxList, write_once, Object,
+};
+
+/// A hash map keyed by value, iterated in insertion order, clones share the same entries
+#[derive(Clone, Default)]
+pub struct LoxMap {
+    entries: Arc<RwLock<Entries>>,
+}
+
+#[derive(Default)]
+struct Entries {
+    keys: Vec<Object>,
+    values: HashMap<Object, Object>,
+}
+
+impl Entries {
+    fn insert(&mut self, key: Object, value: Object) {
+        if self.values.insert(key.clone(), value).is_none() {
+            self.keys.push(key);
+        }
+    }
+    fn remove(&mut self, key: &Object) -> Option<Object> {
+        let value = self.values.remove(key)?;
+        self.keys.retain(|k| k != key);
+        Some(value)
+    }
+}
+
+impl LoxMap {
+    pub(crate) fn new(entries: Vec<(Object, Object)>) -> Result<Self, String> {
+        let map = Self::default();
+        for (key, value) in entries {
+            map.set_index(&key, value)?;
+        }
+        Ok(map)
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.entries.try_read().unwrap().keys.len()
+    }
+    /// A copy of the current entries, in insertion order
+    pub(crate) fn to_vec(&self) -> Vec<(Object, Object)> {
+        let entries = self.entries.try_read().unwrap();
+        entries
+            .keys
+            .iter()
+            .map(|key| (key.clone(), entries.values[key].clone()))
+            .collect()
+    }
+    pub(crate) fn ptr_eq(&self, other: &LoxMap) -> bool {
+        Arc::ptr_eq(&self.entries, &other.entries)
+    }
+    /// The values if this is the last clone, leaving the map empty
+    pub(super) fn take_unique(&mut self) -> Vec<Object> {
+        Arc::get_mut(&mut self.entries)
+            .map(|entries| {
+                let entries = std::mem::take(entries.get_mut().unwrap());
+                entries.values.into_values().collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn get_index(&self, key: &Object) -> Result<Object, String> {
+        check_key(key)?;
+        self.entries
+            .try_read()
+            .unwrap()
+            .values
+            .get(key)
+            .cloned()
+            .ok_or_else(|| undefined_key(key))
+    }
+    pub(crate) fn set_index(&self, key: &Object, value: Object) -> Result<(), String> {
+        check_key(key)?;
+        self.entries.try_write().unwrap().insert(key.clone(), value);
+        Ok(())
+    }
+
+    /// The method `name` bound to this map
+    pub(crate) fn get(&self, name: &Token) -> Object {
+        let map = self.clone();
+        let method = match name.lexeme.as_str() {
+            "len" => {
+                NativeFunction::new("len".into(), 0, move |_| Ok((map.len() as f64).into_lox()))
+            }
+            "has" => NativeFunction::new("has".into(), 1, move |arguments| {
+                check_key(&arguments[0])?;
+                let entries = map.entries.try_read().unwrap();
+                Ok(entries.values.contains_key(&arguments[0]).into_lox())
+            }),
+            "keys" => NativeFunction::new("keys".into(), 0, move |_| {
+                let keys = map.entries.try_read().unwrap().keys.clone();
+                Ok(ar!(ObjectInner::List(LoxList::new(keys))))
+            }),
+            "values" => NativeFunction::new("values".into(), 0, move |_| {
+                let entries = map.entries.try_read().unwrap();
+                let values = entries.keys.iter().map(|k| entries.values[k].clone());
+                Ok(ar!(ObjectInner::List(LoxList::new(values.collect()))))
+            }),
+            "remove" => NativeFunction::new("remove".into(), 1, move |arguments| {
+                check_key(&arguments[0])?;
+                map.entries
+                    .try_write()
+                    .unwrap()
+                    .remove(&arguments[0])
+                    .ok_or_else(|| undefined_key(&arguments[0]))
+            }),
+            _ => throw(RuntimeError::new(
+                name.clone(),
+                format!("Undefined property '{}'.", name.lexeme),
+            )),
+        };
+        obj!(method; @rr ObjectInner::Function)
+    }
+}
+
+/// By identity, like `ptr_eq`
+impl std::hash::Hash for LoxMap {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.entries).hash(state)
+    }
+}
+
+impl Drop for LoxMap {
+    fn drop(&mut self) {
+        drop_nested(self.take_unique());
+    }
+}
+
+impl std::fmt::Display for LoxMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_once(Arc::as_ptr(&self.entries), f, "{...}", |f| {
+            let entries = self.entries.try_read().unwrap();
+            write!(f, "{{")?;
+            for (idx, key) in entries.keys.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}: {}", key, entries.values[key])?;
+            }
+            write!(f, "}}")
+        })
+    }
+}
+impl std::fmt::Debug for LoxMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxMap {}", self)
+    }
+}
+
+/// Keys are the values compared by value, containers and instances are not
+fn check_key(key: &Object) -> Result<(), String> {
+    match key.0 {
+        ObjectInner::Number(n) if n.is_nan() => Err("Map key can't be NaN.".into()),
+        ObjectInner::Number(_)
+        | ObjectInner::String(_)
+        | ObjectInner::Bool(_)
+        | ObjectInner::Null => Ok(()),
+        _ => Err(format!(
+            "Map key must be a string, number, bool or nil, found {}.",
+            key.type_name()
+        )),
+    }
+}
+
+fn undefined_key(key: &Object) -> String {
+    format!("Undefined key '{}'.", key)
+}
@@ -1,10 +1,14 @@
 use std::{
-    io::{self, Write},
+    io::{self, IsTerminal, Write},
+    panic::UnwindSafe,
     path::Path,
-    process,
+    sync::{Arc, Mutex},
+    thread,
 };
 
 mod ast;
+mod diagnostic;
+mod error;
 mod expr;
 mod interpreter;
 mod parser;
@@ -12,69 +16,234 @@ mod resolver;
 mod scanner;
 mod stmt;
 pub mod uuid;
-use interpreter::Interpreter;
+use interpreter::AbortException;
 use parser::Parser;
 use resolver::Resolver;
 use scanner::Scanner;
 use trycatch::{catch, CatchError, ExceptionDowncast};
 
-use crate::interpreter::RuntimeError;
+pub use crate::error::{LoxError, SyntaxError};
+pub use crate::interpreter::{
+    Abort, Frame, FromLox, FromLoxError, Interpreter, InterruptHandle, IntoLox, LoxCallable,
+    LoxClass, LoxInstance, LoxList, LoxMap, LoxTrait, Object, ObjectInner, RuntimeError,
+    DEFAULT_MAX_CALL_DEPTH,
+};
+pub use crate::scanner::Span;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-#[derive(Default)]
+/// Destination for program output or diagnostics, shared so the host can keep a handle to it
+pub type Writer = Arc<Mutex<dyn Write + Send>>;
+
 pub struct Lox {
     interpreter: Interpreter,
+    diagnostics: Writer,
+    color: bool,
+    // Code of every run, indexed by `Span::source`. Functions from any earlier run can still fail
+    sources: Vec<String>,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        let mut lox = Self::new(
+            Arc::new(Mutex::new(io::stdout())),
+            Arc::new(Mutex::new(io::stderr())),
+        );
+        lox.set_color(io::stderr().is_terminal());
+        lox
+    }
 }
 
 impl Lox {
-    pub fn run(&mut self, code: &str) {
+    /// `output` receives what print statements write, `diagnostics` receives every reported error
+    pub fn new(output: Writer, diagnostics: Writer) -> Self {
+        Self {
+            interpreter: Interpreter::new(output),
+            diagnostics,
+            color: false,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Highlight diagnostics with ANSI colours, off unless created by `Lox::default` on a terminal
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    /// Expose a host function to scripts as the global `name`
+    ///
+    /// Returning `Err(message)` raises a runtime error at the call site
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(Vec<Object>) -> std::result::Result<Object, String> + Send + Sync + 'static,
+    ) {
+        self.interpreter.define_native(name, arity, function);
+    }
+
+    pub fn run(&mut self, code: &str) -> std::result::Result<(), LoxError> {
+        self.sources.push(code.to_string());
+
         // scanner
-        let mut scanner = Scanner::new(code.to_string());
+        let mut scanner = Scanner::new(code.to_string(), self.sources.len() - 1);
         let tokens = scanner.scan_tokens();
-        if scanner.had_error {
-            process::exit(65)
+        if !scanner.errors.is_empty() {
+            self.report_all(&scanner.errors);
+            return Err(LoxError::Scan(scanner.errors));
         }
 
         // parser
         let mut parser = Parser::new(tokens);
         let stmts = parser.parse();
-        if parser.had_error {
-            process::exit(65)
+        let errors = parser.errors();
+        if !errors.is_empty() {
+            self.report_all(&errors);
+            return Err(LoxError::Parse(errors));
         }
 
         // resolver
         let mut resolver = Resolver::new(self.interpreter.clone());
         resolver.resolve_stmts(&stmts);
 
-        if resolver.had_error {
-            process::exit(65)
+        if !resolver.errors.is_empty() {
+            self.report_all(&resolver.errors);
+            return Err(LoxError::Resolve(resolver.errors));
         }
 
         // interpreter
+        self.execute(move |interpreter| interpreter.interpret(stmts))
+    }
+
+    /// Limit every `run` or `call` to `budget` executed statements, `None` removes the limit
+    pub fn set_step_budget(&mut self, budget: Option<usize>) {
+        self.interpreter.set_step_budget(budget);
+    }
+
+    /// Calls nested deeper than `depth` raise a "Stack overflow." runtime error
+    ///
+    /// Each call takes up to 15 KB of native stack, the default fits an 8 MiB stack. Spawned threads
+    /// get 2 MiB unless configured otherwise, lower the depth to 64 there or run with
+    /// `with_stack_size`. Raise the limit together with `with_stack_size` too
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.interpreter.max_call_depth = depth;
+    }
+
+    /// Run `f` on a new thread with a `stack_size` bytes stack and wait for it
+    pub fn with_stack_size<R: Send>(
+        &mut self,
+        stack_size: usize,
+        f: impl FnOnce(&mut Lox) -> R + Send,
+    ) -> io::Result<R> {
+        thread::scope(|scope| {
+            let handle = thread::Builder::new()
+                .stack_size(stack_size)
+                .spawn_scoped(scope, || f(self))?;
+            Ok(handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+        })
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interpreter.interrupt_handle()
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Object> {
+        self.interpreter.get_global(name)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: impl IntoLox) {
+        self.interpreter.define_global(name, value.into_lox());
+    }
+
+    /// Call the global function or class `name`, typically a handler defined by an earlier `run`
+    pub fn call(
+        &mut self,
+        name: &str,
+        arguments: Vec<Object>,
+    ) -> std::result::Result<Object, LoxError> {
+        let callee = self
+            .get_global(name)
+            .ok_or_else(|| LoxError::Call(format!("Undefined variable '{}'.", name)))?;
+        let function = Interpreter::callable(&callee)
+            .ok_or_else(|| LoxError::Call("Can only call functions and classes.".into()))?;
+        let arity = function.try_read().unwrap().arity();
+        if arguments.len() != arity {
+            return Err(LoxError::Call(format!(
+                "Expected {} arguments but got {}.",
+                arity,
+                arguments.len()
+            )));
+        }
+        self.execute(move |interpreter| {
+            interpreter.call_from_host(&*function.try_read().unwrap(), arguments)
+        })?
+        .map_err(LoxError::Call)
+    }
+
+    /// Run `f` against the interpreter, turning an escaping runtime error into a `LoxError`
+    fn execute<R>(
+        &mut self,
+        f: impl FnOnce(&mut Interpreter) -> R + UnwindSafe,
+    ) -> std::result::Result<R, LoxError> {
+        self.interpreter.reset_steps();
+        // drop frames an aborted script left behind
+        self.interpreter.take_frames();
         let mut interpreter = self.interpreter.clone();
-        let interpret_result = catch(move || {
-            interpreter.interpret(stmts);
-            interpreter
+        let result = catch(move || {
+            let value = f(&mut interpreter);
+            (interpreter, value)
         });
-        match interpret_result {
-            Ok(interpreter) => self.interpreter = interpreter,
-            Err(CatchError::Exception(e)) => {
-                let runtime_error: RuntimeError = e.downcast();
-                eprintln!("{}", runtime_error.to_string());
-                process::exit(70);
+        match result {
+            Ok((interpreter, value)) => {
+                self.interpreter = interpreter;
+                Ok(value)
             }
+            Err(CatchError::Exception(e)) => match e.try_downcast::<AbortException>() {
+                Ok(abort) => Err(LoxError::Aborted(abort.0)),
+                Err(exception) => {
+                    let mut runtime_error = exception.downcast::<RuntimeError>().unwrap();
+                    runtime_error.trace = self.interpreter.take_frames();
+                    self.report(runtime_error.diagnostic());
+                    self.report_trace(runtime_error.trace());
+                    Err(LoxError::Runtime(runtime_error))
+                }
+            },
             Err(e) => panic!("{:?}", e),
         }
     }
 
-    pub fn run_file<P: AsRef<Path>>(&mut self, file: P) -> Result<()> {
+    fn report_all(&self, errors: &[SyntaxError]) {
+        for error in errors {
+            self.report(error.diagnostic());
+        }
+    }
+
+    fn report(&self, diagnostic: diagnostic::Diagnostic) {
+        let source = self.sources.get(diagnostic.span.source);
+        let rendered = diagnostic.render(source.map_or("", String::as_str), self.color);
+        let _ = write!(self.diagnostics.lock().unwrap(), "{}", rendered);
+    }
+
+    fn report_trace(&self, trace: &[Frame]) {
+        if trace.is_empty() {
+            return;
+        }
+        let mut diagnostics = self.diagnostics.lock().unwrap();
+        let _ = writeln!(diagnostics, "stack traceback:");
+        for frame in trace {
+            let _ = writeln!(diagnostics, "  {}", frame);
+        }
+    }
+
+    pub fn run_file<P: AsRef<Path>>(&mut self, file: P) -> std::result::Result<(), LoxError> {
         let code = std::fs::read_to_string(file)?;
-        self.run(&code);
-        Ok(())
+        self.run(&code)
     }
 
-    pub fn run_prompt(&mut self) -> Result<()> {
+    /// Errors in a line are reported and the prompt keeps going, only io errors end it
+    pub fn run_prompt(&mut self) -> std::result::Result<(), LoxError> {
         let mut line = String::new();
         loop {
             print!("> ");
@@ -92,7 +261,8 @@ impl Lox {
                     code.to_string()
                 }
             };
-            self.run(&repl_it(code));
+            // the diagnostics were already reported
+            let _ = self.run(&repl_it(code));
             line.clear();
         }
         Ok(())
@@ -1,14 +1,30 @@
-use jlox::{Lox, Result};
+use std::process;
 
-fn main() -> Result<()> {
+use jlox::{Lox, LoxError};
+
+/// What the default call depth needs, main thread stacks are smaller on some platforms
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+fn main() {
     let mut lox = Lox::default();
     let args: Vec<_> = std::env::args().skip(1).collect();
-    match args.len() {
-        0 => lox.run_prompt(),
-        1 => lox.run_file(&args[0]),
-        _ => {
-            println!("Usage: jlox [script]");
-            Ok(())
+    let result = lox
+        .with_stack_size(STACK_SIZE, |lox| match args.len() {
+            0 => lox.run_prompt(),
+            1 => lox.run_file(&args[0]),
+            _ => {
+                println!("Usage: jlox [script]");
+                Ok(())
+            }
+        })
+        .unwrap_or_else(|e| Err(LoxError::Io(e)));
+    match result {
+        Ok(()) => (),
+        Err(LoxError::Scan(_) | LoxError::Parse(_) | LoxError::Resolve(_)) => process::exit(65),
+        Err(LoxError::Runtime(_) | LoxError::Call(_) | LoxError::Aborted(_)) => process::exit(70),
+        Err(LoxError::Io(e)) => {
+            eprintln!("{}", e);
+            process::exit(74)
         }
     }
 }
@@ -1,21 +1,23 @@
 use std::fmt;
 use std::sync::atomic::{self, AtomicUsize};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use trycatch::{catch, throw, CatchError, Exception, ExceptionDowncast};
 
+use crate::error::SyntaxError;
 use crate::expr::{self, Expr};
 use crate::interpreter::{Object, ObjectInner};
-use crate::scanner::{Token, TokenType};
+use crate::scanner::{Span, Token, TokenType};
 use crate::stmt::{self, Stmt};
 use crate::{null_obj, obj};
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Parser {
     tokens: Vec<Token>,
     // Needs to be atomic in order to persist between exceptions
     current: Arc<AtomicUsize>,
-    pub had_error: bool,
+    // Shared for the same reason, errors reported from a discarded clone must not be lost
+    errors: Arc<RwLock<Vec<SyntaxError>>>,
 }
 
 impl Parser {
@@ -23,7 +25,7 @@ impl Parser {
         Self {
             tokens,
             current: Default::default(),
-            had_error: false,
+            errors: Default::default(),
         }
     }
     pub fn parse(&mut self) -> Vec<Stmt> {
@@ -31,12 +33,13 @@ impl Parser {
         while !self.is_at_end() {
             if let Some(stmt) = self.declaration() {
                 stmts.push(stmt);
-            } else {
-                self.had_error = true;
             }
         }
         stmts
     }
+    pub fn errors(&self) -> Vec<SyntaxError> {
+        self.errors.try_read().unwrap().clone()
+    }
     fn expression(&mut self) -> Box<expr::Expr> {
         self.assignment()
     }
@@ -47,18 +50,20 @@ impl Parser {
         while self.tmatch([TokenType::BANG_EQUAL, TokenType::EQUAL_EQUAL]) {
             let operator = self.previous().clone();
             let right = self.comparison();
+            let span = expr.span().to(right.span());
             expr = expr::Expr::Binary(expr::Binary {
                 left: expr,
                 operator,
                 right,
+                span,
             })
             .into();
         }
         expr
     }
-    //comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+    //comparison     → bit_or ( ( ">" | ">=" | "<" | "<=" ) bit_or )* ;
     fn comparison(&mut self) -> Box<Expr> {
-        let mut expr = self.term();
+        let mut expr = self.bit_or();
         while self.tmatch([
             TokenType::GREATER,
             TokenType::GREATER_EQUAL,
@@ -66,11 +71,53 @@ impl Parser {
             TokenType::LESS_EQUAL,
         ]) {
             let operator = self.previous().clone();
-            let right = self.term();
+            let right = self.bit_or();
+            let span = expr.span().to(right.span());
             expr = Expr::Binary(expr::Binary {
                 left: expr,
                 operator,
                 right,
+                span,
+            })
+            .into();
+        }
+        expr
+    }
+    //bit_or         → bit_xor ( "|" bit_xor )* ;
+    fn bit_or(&mut self) -> Box<Expr> {
+        self.left_associative([TokenType::PIPE], Self::bit_xor)
+    }
+    //bit_xor        → bit_and ( "^" bit_and )* ;
+    fn bit_xor(&mut self) -> Box<Expr> {
+        self.left_associative([TokenType::CARET], Self::bit_and)
+    }
+    //bit_and        → shift ( "&" shift )* ;
+    fn bit_and(&mut self) -> Box<Expr> {
+        self.left_associative([TokenType::AMPERSAND], Self::shift)
+    }
+    //shift          → term ( ( "<<" | ">>" ) term )* ;
+    fn shift(&mut self) -> Box<Expr> {
+        self.left_associative(
+            [TokenType::LESS_LESS, TokenType::GREATER_GREATER],
+            Self::term,
+        )
+    }
+    /// A chain of binary `operators` between `operand`s, grouped to the left
+    fn left_associative<const N: usize>(
+        &mut self,
+        operators: [TokenType; N],
+        operand: fn(&mut Self) -> Box<Expr>,
+    ) -> Box<Expr> {
+        let mut expr = operand(self);
+        while self.tmatch(operators.clone()) {
+            let operator = self.previous().clone();
+            let right = operand(self);
+            let span = expr.span().to(right.span());
+            expr = Expr::Binary(expr::Binary {
+                left: expr,
+                operator,
+                right,
+                span,
             })
             .into();
         }
@@ -81,10 +128,12 @@ impl Parser {
         while self.tmatch([TokenType::MINUS, TokenType::PLUS]) {
             let operator = self.previous().clone();
             let right = self.factor();
+            let span = expr.span().to(right.span());
             expr = Expr::Binary(expr::Binary {
                 left: expr,
                 operator,
                 right,
+                span,
             })
             .into();
         }
@@ -92,47 +141,122 @@ impl Parser {
     }
     fn factor(&mut self) -> Box<Expr> {
         let mut expr = self.unary();
-        while self.tmatch([TokenType::SLASH, TokenType::STAR]) {
+        while self.tmatch([TokenType::SLASH, TokenType::STAR, TokenType::PERCENT]) {
             let operator = self.previous().clone();
             let right = self.unary();
+            let span = expr.span().to(right.span());
             expr = Expr::Binary(expr::Binary {
                 left: expr,
                 operator,
                 right,
+                span,
             })
             .into();
         }
         expr
     }
-    //unary          → ( "!" | "-" ) unary | primary ;
+    //unary          → ( "!" | "-" | "~" ) unary | ( "++" | "--" ) unary | power ;
     fn unary(&mut self) -> Box<Expr> {
-        if self.tmatch([TokenType::BANG, TokenType::MINUS]) {
+        if self.tmatch([TokenType::PLUS_PLUS, TokenType::MINUS_MINUS]) {
+            let operator = self.previous().clone();
+            let target = self.unary();
+            let span = operator.span().to(target.span());
+            return self.update(target, operator.clone(), Self::one(&operator), false, span);
+        }
+        if self.tmatch([TokenType::BANG, TokenType::MINUS, TokenType::TILDE]) {
             let operator = self.previous().clone();
             let right = self.unary();
-            return Expr::Unary(expr::Unary { operator, right }).into();
+            let span = operator.span().to(right.span());
+            return Expr::Unary(expr::Unary {
+                operator,
+                right,
+                span,
+            })
+            .into();
+        }
+        self.power()
+    }
+    //power          → postfix ( "**" unary )? ;
+    fn power(&mut self) -> Box<Expr> {
+        let expr = self.postfix();
+        // the right side goes through unary again, making ** right-associative
+        if self.tmatch([TokenType::STAR_STAR]) {
+            let operator = self.previous().clone();
+            let right = self.unary();
+            let span = expr.span().to(right.span());
+            return Expr::Binary(expr::Binary {
+                left: expr,
+                operator,
+                right,
+                span,
+            })
+            .into();
         }
-        self.call()
+        expr
     }
-    //primary        → NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")" ;
+    //postfix        → call ( "++" | "--" )? ;
+    fn postfix(&mut self) -> Box<Expr> {
+        let expr = self.call();
+        if self.tmatch([TokenType::PLUS_PLUS, TokenType::MINUS_MINUS]) {
+            let operator = self.previous().clone();
+            let span = expr.span().to(operator.span());
+            return self.update(expr, operator.clone(), Self::one(&operator), true, span);
+        }
+        expr
+    }
+    //primary        → NUMBER | STRING | interpolation | lambda | list | map | "true" | "false" | "nil" | "(" expression ")" ;
     fn primary(&mut self) -> Box<Expr> {
         if self.tmatch([TokenType::FALSE]) {
             return Expr::Literal(expr::Literal {
                 value: obj!(false; ObjectInner::Bool),
+                span: self.previous().span(),
             })
             .into();
         }
         if self.tmatch([TokenType::TRUE]) {
             return Expr::Literal(expr::Literal {
                 value: obj!(true; ObjectInner::Bool),
+                span: self.previous().span(),
             })
             .into();
         }
         if self.tmatch([TokenType::NIL]) {
-            return Expr::Literal(expr::Literal { value: null_obj!() }).into();
+            return Expr::Literal(expr::Literal {
+                value: null_obj!(),
+                span: self.previous().span(),
+            })
+            .into();
         }
         if self.tmatch([TokenType::NUMBER, TokenType::STRING]) {
             return Expr::Literal(expr::Literal {
                 value: self.previous().clone().literal,
+                span: self.previous().span(),
+            })
+            .into();
+        }
+        if self.tmatch([TokenType::INTERPOLATION]) {
+            return self.interpolation().into();
+        }
+        if self.tmatch([TokenType::FUN]) {
+            return self.lambda().into();
+        }
+        if self.tmatch([TokenType::LEFT_BRACKET]) {
+            let bracket = self.previous().span();
+            let elements =
+                self.elements(TokenType::RIGHT_BRACKET, "Expect ']' after list elements.");
+            return Expr::List(expr::List {
+                elements,
+                span: bracket.to(self.previous().span()),
+            })
+            .into();
+        }
+        if self.tmatch([TokenType::LEFT_BRACE]) {
+            let brace = self.previous().clone();
+            let entries = self.entries();
+            return Expr::Map(expr::Map {
+                span: brace.span().to(self.previous().span()),
+                brace,
+                entries,
             })
             .into();
         }
@@ -142,28 +266,94 @@ impl Parser {
             let method = self
                 .consume(TokenType::IDENTIFIER, "Expect superclass method name.")
                 .clone();
-            return Expr::Super(expr::Super { keyword, method }).into();
+            let span = keyword.span().to(method.span());
+            return Expr::Super(expr::Super {
+                keyword,
+                method,
+                span,
+            })
+            .into();
         }
         if self.tmatch([TokenType::THIS]) {
             return Expr::This(expr::This {
                 keyword: self.previous().clone(),
+                span: self.previous().span(),
             })
             .into();
         }
         if self.tmatch([TokenType::IDENTIFIER]) {
             return Expr::Variable(expr::Variable {
                 name: self.previous().clone(),
+                span: self.previous().span(),
             })
             .into();
         }
 
         if self.tmatch([TokenType::LEFT_PAREN]) {
+            let paren = self.previous().span();
             let expr = self.expression();
             self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.");
-            return Expr::Grouping(expr::Grouping { expression: expr }).into();
+            return Expr::Grouping(expr::Grouping {
+                expression: expr,
+                span: paren.to(self.previous().span()),
+            })
+            .into();
         }
         self.throw_error(self.peek().unwrap(), "Expect expression.");
     }
+    //list           → "[" ( expression ( "," expression )* ","? )? "]" ;
+    fn elements(&mut self, closing: TokenType, message: &str) -> Vec<Expr> {
+        let mut elements = vec![];
+        while !self.check(closing.clone()) {
+            elements.push(*self.expression());
+            if !self.tmatch(TokenType::COMMA) {
+                break;
+            }
+        }
+        self.consume(closing, message);
+        elements
+    }
+    //map            → "{" ( expression ":" expression ( "," expression ":" expression )* ","? )? "}" ;
+    fn entries(&mut self) -> Vec<(Expr, Expr)> {
+        let mut entries = vec![];
+        while !self.check(TokenType::RIGHT_BRACE) {
+            let key = *self.expression();
+            self.consume(TokenType::COLON, "Expect ':' after map key.");
+            entries.push((key, *self.expression()));
+            if !self.tmatch(TokenType::COMMA) {
+                break;
+            }
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after map entries.");
+        entries
+    }
+    //interpolation  → ( INTERPOLATION expression )+ STRING ;
+    fn interpolation(&mut self) -> Expr {
+        let start = self.previous().span();
+        let mut parts = vec![];
+        loop {
+            parts.push(Expr::Literal(expr::Literal {
+                value: self.previous().literal.clone(),
+                span: self.previous().span(),
+            }));
+            parts.push(*self.expression());
+            if !self.tmatch([TokenType::INTERPOLATION]) {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::STRING,
+            "Expect '}' after expression in string interpolation.",
+        );
+        parts.push(Expr::Literal(expr::Literal {
+            value: self.previous().literal.clone(),
+            span: self.previous().span(),
+        }));
+        Expr::Interpolation(expr::Interpolation {
+            parts,
+            span: start.to(self.previous().span()),
+        })
+    }
     fn consume(&mut self, ttype: TokenType, message: impl fmt::Display) -> &Token {
         if self.check(ttype) {
             self.advance()
@@ -171,32 +361,15 @@ impl Parser {
             self.throw_error(self.peek().unwrap(), message)
         }
     }
-    fn report_error(&mut self, token: &Token, message: impl fmt::Display) {
-        self.had_error = true;
-        let token = token;
-        let message = message;
-        if token.ttype == TokenType::EOF {
-            eprintln!("[line {}] Error at end: {}", token.line, message);
-        } else {
-            eprintln!(
-                "[line {}] Error at '{}': {}",
-                token.line, token.lexeme, message
-            );
-        }
+    fn report_error(&self, token: &Token, message: impl fmt::Display) {
+        self.errors
+            .try_write()
+            .unwrap()
+            .push(SyntaxError::at(token, message));
     }
     fn throw_error(&self, token: &Token, message: impl fmt::Display) -> ! {
-        let token = token;
-        let message = message;
-        if token.ttype == TokenType::EOF {
-            eprintln!("[line {}] Error at end: {}", token.line, message);
-            throw(ParseError)
-        } else {
-            eprintln!(
-                "[line {}] Error at '{}': {}",
-                token.line, token.lexeme, message
-            );
-            throw(ParseError)
-        }
+        self.report_error(token, message);
+        throw(ParseError)
     }
     fn synchronize(&mut self) {
         self.advance();
@@ -206,13 +379,16 @@ impl Parser {
             }
             match self.peek().unwrap().ttype {
                 TokenType::CLASS
+                | TokenType::TRAIT
                 | TokenType::FUN
                 | TokenType::VAR
                 | TokenType::FOR
                 | TokenType::IF
                 | TokenType::WHILE
                 | TokenType::PRINT
-                | TokenType::RETURN => return,
+                | TokenType::RETURN
+                | TokenType::BREAK
+                | TokenType::CONTINUE => return,
                 _ => (),
             }
             self.advance();
@@ -234,6 +410,12 @@ impl Parser {
         }
         self.peek().unwrap().ttype == ttype
     }
+    fn check_next(&self, ttype: TokenType) -> bool {
+        self.tokens
+            .get(self.current.load(atomic::Ordering::Relaxed) + 1)
+            .map(|token| token.ttype == ttype)
+            .unwrap_or(false)
+    }
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current.store(
@@ -273,24 +455,46 @@ impl Parser {
         if self.tmatch([TokenType::WHILE]) {
             return self.while_statement();
         }
+        if self.tmatch([TokenType::BREAK]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::SEMICOLON, "Expect ';' after 'break'.");
+            let span = keyword.span().to(self.previous().span());
+            return Stmt::Break(stmt::Break { keyword, span });
+        }
+        if self.tmatch([TokenType::CONTINUE]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::SEMICOLON, "Expect ';' after 'continue'.");
+            let span = keyword.span().to(self.previous().span());
+            return Stmt::Continue(stmt::Continue { keyword, span });
+        }
         if self.tmatch([TokenType::LEFT_BRACE]) {
+            let brace = self.previous().span();
             return Stmt::Block(stmt::Block {
                 statements: self.block(),
+                span: brace.to(self.previous().span()),
             });
         }
         self.expression_statement()
     }
 
     fn print_statement(&mut self) -> Stmt {
+        let keyword = self.previous().span();
         let value = *self.expression();
         self.consume(TokenType::SEMICOLON, "Expect ';' after value.");
-        Stmt::Print(stmt::Print { expression: value })
+        Stmt::Print(stmt::Print {
+            expression: value,
+            span: keyword.to(self.previous().span()),
+        })
     }
 
     fn expression_statement(&mut self) -> Stmt {
         let expr = *self.expression();
         self.consume(TokenType::SEMICOLON, "Expect ';' after expression.");
-        Stmt::Expression(stmt::Expression { expression: expr })
+        let span = expr.span().to(self.previous().span());
+        Stmt::Expression(stmt::Expression {
+            expression: expr,
+            span,
+        })
     }
 
     fn declaration(&mut self) -> Option<Stmt> {
@@ -301,7 +505,12 @@ impl Parser {
                 let val = parser.class_declaration();
                 return (parser, val);
             }
-            if parser.tmatch([TokenType::FUN]) {
+            if parser.tmatch([TokenType::TRAIT]) {
+                let val = parser.trait_declaration();
+                return (parser, val);
+            }
+            // without a name `fun` starts a lambda expression
+            if parser.check_next(TokenType::IDENTIFIER) && parser.tmatch([TokenType::FUN]) {
                 let val = Stmt::Function(parser.function("function"));
                 return (parser, val);
             }
@@ -328,6 +537,7 @@ impl Parser {
     }
 
     fn var_declaration(&mut self) -> Stmt {
+        let keyword = self.previous().span();
         let name = self
             .consume(TokenType::IDENTIFIER, "Expect variable name.")
             .clone();
@@ -337,25 +547,41 @@ impl Parser {
             initializer = Some(*self.expression());
         }
         self.consume(TokenType::SEMICOLON, "Expect ; after variable declaration.");
-        Stmt::Var(stmt::Var { name, initializer })
+        Stmt::Var(stmt::Var {
+            name,
+            initializer,
+            span: keyword.to(self.previous().span()),
+        })
     }
 
     fn assignment(&mut self) -> Box<Expr> {
-        let expr = self.or();
+        let expr = self.conditional();
         if self.tmatch([TokenType::EQUAL]) {
             let equals = self.previous().clone();
             let value = self.assignment();
+            let span = expr.span().to(value.span());
 
             match *expr {
                 Expr::Variable(var) => {
                     let name = var.name;
-                    return Expr::Assign(expr::Assign { name, value }).into();
+                    return Expr::Assign(expr::Assign { name, value, span }).into();
                 }
                 Expr::Get(get) => {
                     return Expr::Set(expr::Set {
                         object: get.object,
                         name: get.name,
                         value,
+                        span,
+                    })
+                    .into();
+                }
+                Expr::Index(index) => {
+                    return Expr::IndexSet(expr::IndexSet {
+                        object: index.object,
+                        bracket: index.bracket,
+                        index: index.index,
+                        value,
+                        span,
                     })
                     .into();
                 }
@@ -363,17 +589,88 @@ impl Parser {
                     self.report_error(&equals, "Invalid assignment target.");
                 }
             }
+        } else if self.tmatch([
+            TokenType::PLUS_EQUAL,
+            TokenType::MINUS_EQUAL,
+            TokenType::STAR_EQUAL,
+            TokenType::SLASH_EQUAL,
+        ]) {
+            let operator = self.previous().clone();
+            let value = self.assignment();
+            let span = expr.span().to(value.span());
+            return self.update(expr, operator, value, false, span);
         }
         expr
     }
 
+    /// `target op= value`, `++`/`--` pass a value of 1, `postfix` evaluates to the old value
+    fn update(
+        &mut self,
+        target: Box<Expr>,
+        operator: Token,
+        value: Box<Expr>,
+        postfix: bool,
+        span: Span,
+    ) -> Box<Expr> {
+        let ttype = match operator.ttype {
+            TokenType::PLUS_EQUAL | TokenType::PLUS_PLUS => TokenType::PLUS,
+            TokenType::MINUS_EQUAL | TokenType::MINUS_MINUS => TokenType::MINUS,
+            TokenType::STAR_EQUAL => TokenType::STAR,
+            TokenType::SLASH_EQUAL => TokenType::SLASH,
+            _ => unreachable!(),
+        };
+        // the binary operator applied, still pointing at the compound one for errors
+        let binary = Token {
+            ttype,
+            ..operator.clone()
+        };
+        match *target {
+            Expr::Variable(var) => Expr::AssignOp(expr::AssignOp {
+                name: var.name,
+                operator: binary,
+                value,
+                postfix,
+                span,
+            })
+            .into(),
+            Expr::Get(get) => Expr::SetOp(expr::SetOp {
+                object: get.object,
+                name: get.name,
+                operator: binary,
+                value,
+                postfix,
+                span,
+            })
+            .into(),
+            Expr::Index(index) => Expr::IndexSetOp(expr::IndexSetOp {
+                object: index.object,
+                bracket: index.bracket,
+                index: index.index,
+                operator: binary,
+                value,
+                postfix,
+                span,
+            })
+            .into(),
+            target => {
+                self.report_error(&operator, "Invalid assignment target.");
+                target.into()
+            }
+        }
+    }
+    fn one(operator: &Token) -> Box<Expr> {
+        Expr::Literal(expr::Literal {
+            value: obj!(1.; ObjectInner::Number),
+            span: operator.span(),
+        })
+        .into()
+    }
+
     fn block(&mut self) -> Vec<Stmt> {
         let mut statements = vec![];
         while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
             if let Some(stmt) = self.declaration() {
                 statements.push(stmt);
-            } else {
-                self.had_error = true;
             }
         }
         self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.");
@@ -381,31 +678,57 @@ impl Parser {
     }
 
     fn if_statement(&mut self) -> Stmt {
+        let keyword = self.previous().span();
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.");
         let condition = *self.expression();
         self.consume(TokenType::RIGHT_PAREN, "Expect ')' after if condition.");
 
-        let then_branch = self.statement().into();
-        let mut else_branch = None;
+        let then_branch: Box<Stmt> = self.statement().into();
+        let mut else_branch: Option<Box<Stmt>> = None;
         if self.tmatch([TokenType::ELSE]) {
             else_branch = Some(self.statement().into());
         }
+        let span = keyword.to(else_branch.as_ref().unwrap_or(&then_branch).span());
         Stmt::If(stmt::If {
             condition,
             then_branch,
             else_branch,
+            span,
         })
     }
 
+    //conditional    → logic_or ( "?" expression ":" conditional )? ;
+    fn conditional(&mut self) -> Box<Expr> {
+        let condition = self.or();
+        if self.tmatch([TokenType::QUESTION]) {
+            let then_branch = self.expression();
+            self.consume(
+                TokenType::COLON,
+                "Expect ':' after then branch of conditional expression.",
+            );
+            let else_branch = self.conditional();
+            let span = condition.span().to(else_branch.span());
+            return Expr::Conditional(expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                span,
+            })
+            .into();
+        }
+        condition
+    }
     fn or(&mut self) -> Box<Expr> {
         let mut expr = self.and();
         while self.tmatch([TokenType::OR]) {
             let operator = self.previous().clone();
             let right = self.and();
+            let span = expr.span().to(right.span());
             expr = Expr::Logical(expr::Logical {
                 left: expr,
                 operator,
                 right,
+                span,
             })
             .into();
         }
@@ -417,10 +740,12 @@ impl Parser {
         while self.tmatch([TokenType::AND]) {
             let operator = self.previous().clone();
             let right = self.equality();
+            let span = expr.span().to(right.span());
             expr = Expr::Logical(expr::Logical {
                 left: expr,
                 operator,
                 right,
+                span,
             })
             .into();
         }
@@ -428,15 +753,23 @@ impl Parser {
     }
 
     fn while_statement(&mut self) -> Stmt {
+        let keyword = self.previous().span();
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'while'");
         let condition = *self.expression();
         self.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition");
-        let body = self.statement().into();
+        let body = self.statement();
+        let span = keyword.to(body.span());
 
-        Stmt::While(stmt::While { condition, body })
+        Stmt::While(stmt::While {
+            condition,
+            body: body.into(),
+            increment: None,
+            span,
+        })
     }
 
     fn for_statement(&mut self) -> Stmt {
+        let keyword = self.previous().span();
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'");
         let initializer = if self.tmatch(TokenType::SEMICOLON) {
             None
@@ -461,33 +794,29 @@ impl Parser {
         self.consume(TokenType::RIGHT_PAREN, "Expect ')' after clauses.");
 
         let mut body = self.statement();
+        let span = keyword.to(body.span());
 
-        if let Some(increment) = increment {
-            body = Stmt::Block(stmt::Block {
-                statements: vec![
-                    body,
-                    Stmt::Expression(stmt::Expression {
-                        expression: *increment,
-                    }),
-                ],
-            });
-        }
         let condition = if let Some(condition) = condition {
             *condition
         } else {
             Expr::Literal(expr::Literal {
                 value: obj!(true; ObjectInner::Bool),
+                span: keyword,
             })
         };
 
+        // the increment is kept apart from the body so it still runs after a continue
         body = Stmt::While(stmt::While {
             condition,
             body: body.into(),
+            increment,
+            span,
         });
 
         if let Some(initializer) = initializer {
             body = Stmt::Block(stmt::Block {
                 statements: vec![initializer, body],
+                span,
             });
         }
 
@@ -500,11 +829,30 @@ impl Parser {
         loop {
             if self.tmatch(TokenType::LEFT_PAREN) {
                 expr = self.finish_call(expr).into();
+            } else if self.tmatch(TokenType::LEFT_BRACKET) {
+                let index = self.expression();
+                let bracket = self
+                    .consume(TokenType::RIGHT_BRACKET, "Expect ']' after index.")
+                    .clone();
+                let span = expr.span().to(bracket.span());
+                expr = Expr::Index(expr::Index {
+                    object: expr,
+                    bracket,
+                    index,
+                    span,
+                })
+                .into();
             } else if self.tmatch(TokenType::DOT) {
                 let name = self
                     .consume(TokenType::IDENTIFIER, "Expect property name after '.'.")
                     .clone();
-                expr = Expr::Get(expr::Get { object: expr, name }).into();
+                let span = expr.span().to(name.span());
+                expr = Expr::Get(expr::Get {
+                    object: expr,
+                    name,
+                    span,
+                })
+                .into();
             } else {
                 break;
             }
@@ -526,11 +874,13 @@ impl Parser {
         let paren = self
             .consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.")
             .clone();
+        let span = callee.span().to(paren.span());
 
         Expr::Call(expr::Call {
             callee,
             paren,
             arguemnts,
+            span,
         })
     }
 
@@ -538,10 +888,56 @@ impl Parser {
         let name = self
             .consume(TokenType::IDENTIFIER, format!("Expect {} name.", kind))
             .clone();
+        // a method without a parameter list is a getter
+        if kind == "method" && self.tmatch(TokenType::LEFT_BRACE) {
+            let body = self.block();
+            let span = name.span().to(self.previous().span());
+            return stmt::Function {
+                name,
+                params: vec![],
+                body,
+                getter: true,
+                span,
+            };
+        }
         self.consume(
             TokenType::LEFT_PAREN,
             format!("Expect '(' after {} name.", kind),
         );
+        let (params, body) = self.parameters_and_body(kind);
+        let span = name.span().to(self.previous().span());
+        stmt::Function {
+            name,
+            params,
+            body,
+            getter: false,
+            span,
+        }
+    }
+    //lambda         → "fun" "(" parameters? ")" block ;
+    fn lambda(&mut self) -> Expr {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'fun'.");
+        let (params, body) = self.parameters_and_body("lambda");
+        let span = keyword.span().to(self.previous().span());
+        // named after what it is, so it shows up as <fn lambda>
+        let name = Token {
+            lexeme: "lambda".into(),
+            ..keyword
+        };
+        Expr::Lambda(expr::Lambda {
+            function: stmt::Function {
+                name,
+                params,
+                body,
+                getter: false,
+                span,
+            },
+            span,
+        })
+    }
+    /// Everything after the '(' of a function
+    fn parameters_and_body(&mut self, kind: &str) -> (Vec<Token>, Vec<Stmt>) {
         let mut params = vec![];
         if !self.check(TokenType::RIGHT_PAREN) {
             params.push(
@@ -565,7 +961,7 @@ impl Parser {
         );
 
         let body = self.block();
-        stmt::Function { name, params, body }
+        (params, body)
     }
 
     fn return_statement(&mut self) -> Stmt {
@@ -575,10 +971,16 @@ impl Parser {
             value = Some(*self.expression());
         }
         self.consume(TokenType::SEMICOLON, "Expect ';' after return value.");
-        Stmt::Return(stmt::Return { keyword, value })
+        let span = keyword.span().to(self.previous().span());
+        Stmt::Return(stmt::Return {
+            keyword,
+            value,
+            span,
+        })
     }
 
     fn class_declaration(&mut self) -> Stmt {
+        let keyword = self.previous().span();
         let name = self
             .consume(TokenType::IDENTIFIER, "Expect class name")
             .clone();
@@ -588,20 +990,62 @@ impl Parser {
             self.consume(TokenType::IDENTIFIER, "Expect superclass name.");
             superclass = Some(expr::Variable {
                 name: self.previous().clone(),
+                span: self.previous().span(),
             });
         }
 
+        let mut traits = vec![];
+        if self.tmatch(TokenType::WITH) {
+            loop {
+                self.consume(TokenType::IDENTIFIER, "Expect trait name.");
+                traits.push(expr::Variable {
+                    name: self.previous().clone(),
+                    span: self.previous().span(),
+                });
+                if !self.tmatch(TokenType::COMMA) {
+                    break;
+                }
+            }
+        }
+
         self.consume(TokenType::LEFT_BRACE, "Expect '{' before class body.");
 
         let mut methods = vec![];
+        let mut class_methods = vec![];
         while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
-            methods.push(self.function("method"));
+            if self.tmatch(TokenType::CLASS) {
+                class_methods.push(self.function("method"));
+            } else {
+                methods.push(self.function("method"));
+            }
         }
         self.consume(TokenType::RIGHT_BRACE, "Expect '}' after class body.");
         Stmt::Class(stmt::Class {
             name,
             superclass,
+            traits,
+            methods,
+            class_methods,
+            span: keyword.to(self.previous().span()),
+        })
+    }
+
+    fn trait_declaration(&mut self) -> Stmt {
+        let keyword = self.previous().span();
+        let name = self
+            .consume(TokenType::IDENTIFIER, "Expect trait name.")
+            .clone();
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' before trait body.");
+
+        let mut methods = vec![];
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            methods.push(self.function("method"));
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after trait body.");
+        Stmt::Trait(stmt::Trait {
+            name,
             methods,
+            span: keyword.to(self.previous().span()),
         })
     }
 }
@@ -1,8 +1,9 @@
 use core::fmt;
 use std::collections::HashMap;
 
+use crate::error::SyntaxError;
 use crate::interpreter::Interpreter;
-use crate::scanner::{Token, TokenType};
+use crate::scanner::{Span, Token};
 use crate::{expr, stmt};
 
 pub struct Resolver {
@@ -10,7 +11,9 @@ pub struct Resolver {
     scopes: Vec<HashMap<String, bool>>,
     current_function: FunctionType,
     current_class: ClassType,
-    pub had_error: bool,
+    // Spans of the loops enclosing the current statement, within the current function
+    loops: Vec<Span>,
+    pub errors: Vec<SyntaxError>,
 }
 
 #[derive(Clone, Copy)]
@@ -26,6 +29,7 @@ enum ClassType {
     None,
     Class,
     SubClass,
+    Trait,
 }
 
 impl stmt::Visit<()> for Resolver {
@@ -79,7 +83,26 @@ impl stmt::Visit<()> for Resolver {
 
     fn visit_while_stmt(&mut self, stmt: &stmt::While) {
         self.resolve_expr(&stmt.condition);
+        self.loops.push(stmt.span);
         self.resolve_stmt(&stmt.body);
+        self.loops.pop();
+        if let Some(ref increment) = stmt.increment {
+            self.resolve_expr(increment);
+        }
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &stmt::Break) {
+        match self.loops.last() {
+            Some(&span) => self.interpreter.resolve_jump(span),
+            None => self.report_error(&stmt.keyword, "Can't use 'break' outside of a loop."),
+        }
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &stmt::Continue) {
+        match self.loops.last() {
+            Some(&span) => self.interpreter.resolve_jump(span),
+            None => self.report_error(&stmt.keyword, "Can't use 'continue' outside of a loop."),
+        }
     }
 
     fn visit_class_stmt(&mut self, stmt: &stmt::Class) {
@@ -95,6 +118,15 @@ impl stmt::Visit<()> for Resolver {
             self.current_class = ClassType::SubClass;
             self.resolve_expr(&superclass.clone().into());
         }
+        for (idx, lox_trait) in stmt.traits.iter().enumerate() {
+            if stmt.traits[..idx]
+                .iter()
+                .any(|other| other.name.lexeme == lox_trait.name.lexeme)
+            {
+                self.report_error(&lox_trait.name, "A class can't use the same trait twice.")
+            }
+            self.resolve_expr(&lox_trait.clone().into());
+        }
         if stmt.superclass.is_some() {
             self.begin_scope();
             self.scopes.last_mut().unwrap().insert("super".into(), true);
@@ -114,6 +146,9 @@ impl stmt::Visit<()> for Resolver {
             };
             self.resolve_function(method, declaration);
         }
+        for method in &stmt.class_methods {
+            self.resolve_function(method, FunctionType::Method);
+        }
 
         self.end_scope();
         if stmt.superclass.is_some() {
@@ -122,6 +157,31 @@ impl stmt::Visit<()> for Resolver {
 
         self.current_class = enclosing_class;
     }
+
+    fn visit_trait_stmt(&mut self, stmt: &stmt::Trait) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Trait;
+
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        self.begin_scope();
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert("this".to_string(), true);
+        for method in &stmt.methods {
+            let declaration = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, declaration);
+        }
+        self.end_scope();
+
+        self.current_class = enclosing_class;
+    }
 }
 
 impl expr::Visit<()> for Resolver {
@@ -142,6 +202,13 @@ impl expr::Visit<()> for Resolver {
         self.resolve_local(&expr::Expr::Assign(expr.clone()), &expr.name);
     }
 
+    fn visit_assign_op_expr(&mut self, expr: &expr::AssignOp) {
+        self.resolve_expr(&expr.value);
+        // the variable is read and written through the same resolved distance
+        self.check_initialized(&expr.name);
+        self.resolve_local(&expr::Expr::AssignOp(expr.clone()), &expr.name);
+    }
+
     fn visit_grouping_expr(&mut self, expr: &expr::Grouping) {
         self.resolve_expr(&expr.expression);
     }
@@ -150,6 +217,22 @@ impl expr::Visit<()> for Resolver {
         //noop
     }
 
+    fn visit_interpolation_expr(&mut self, expr: &expr::Interpolation) {
+        for part in &expr.parts {
+            self.resolve_expr(part);
+        }
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &expr::Lambda) {
+        self.resolve_function(&expr.function, FunctionType::Function);
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &expr::Conditional) {
+        self.resolve_expr(&expr.condition);
+        self.resolve_expr(&expr.then_branch);
+        self.resolve_expr(&expr.else_branch);
+    }
+
     fn visit_logical_expr(&mut self, expr: &expr::Logical) {
         self.resolve_expr(&expr.left);
         self.resolve_expr(&expr.right);
@@ -160,20 +243,7 @@ impl expr::Visit<()> for Resolver {
     }
 
     fn visit_variable_expr(&mut self, expr: &expr::Variable) {
-        if !self.scopes.is_empty()
-            && self
-                .scopes
-                .last()
-                .unwrap()
-                .get(&expr.name.lexeme)
-                .map(|initialized| initialized == &false)
-                .unwrap_or(false)
-        {
-            self.report_error(
-                &expr.name,
-                "Can't read local variable in its own initializer.",
-            )
-        }
+        self.check_initialized(&expr.name);
         self.resolve_local(&expr::Expr::Variable(expr.clone()), &expr.name);
     }
 
@@ -186,6 +256,41 @@ impl expr::Visit<()> for Resolver {
         self.resolve_expr(&expr.object);
     }
 
+    fn visit_index_expr(&mut self, expr: &expr::Index) {
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &expr::IndexSet) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_index_set_op_expr(&mut self, expr: &expr::IndexSetOp) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_list_expr(&mut self, expr: &expr::List) {
+        for element in &expr.elements {
+            self.resolve_expr(element);
+        }
+    }
+
+    fn visit_map_expr(&mut self, expr: &expr::Map) {
+        for (key, value) in &expr.entries {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_set_op_expr(&mut self, expr: &expr::SetOp) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+    }
+
     fn visit_this_expr(&mut self, expr: &expr::This) {
         if matches!(self.current_class, ClassType::None) {
             self.report_error(&expr.keyword, "Can't use 'this' outside of a class.")
@@ -196,6 +301,8 @@ impl expr::Visit<()> for Resolver {
     fn visit_super_expr(&mut self, expr: &expr::Super) {
         if matches!(self.current_class, ClassType::None) {
             self.report_error(&expr.keyword, "Can't use 'super' outside of a class.\n");
+        } else if matches!(self.current_class, ClassType::Trait) {
+            self.report_error(&expr.keyword, "Can't use 'super' in a trait.\n");
         } else if !matches!(self.current_class, ClassType::SubClass) {
             self.report_error(
                 &expr.keyword,
@@ -208,17 +315,7 @@ impl expr::Visit<()> for Resolver {
 
 impl Resolver {
     fn report_error(&mut self, token: &Token, message: impl fmt::Display) {
-        self.had_error = true;
-        let token = token;
-        let message = message;
-        if token.ttype == TokenType::EOF {
-            eprintln!("[line {}] Error at end: {}", token.line, message);
-        } else {
-            eprintln!(
-                "[line {}] Error at '{}': {}",
-                token.line, token.lexeme, message
-            );
-        }
+        self.errors.push(SyntaxError::at(token, message));
     }
     pub fn new(interpreter: Interpreter) -> Self {
         Self {
@@ -226,7 +323,8 @@ impl Resolver {
             scopes: vec![],
             current_function: FunctionType::None,
             current_class: ClassType::None,
-            had_error: false,
+            loops: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
@@ -275,6 +373,20 @@ impl Resolver {
             .insert(name.lexeme.clone(), true);
     }
 
+    fn check_initialized(&mut self, name: &Token) {
+        if !self.scopes.is_empty()
+            && self
+                .scopes
+                .last()
+                .unwrap()
+                .get(&name.lexeme)
+                .map(|initialized| initialized == &false)
+                .unwrap_or(false)
+        {
+            self.report_error(name, "Can't read local variable in its own initializer.")
+        }
+    }
+
     fn resolve_local(&mut self, expr: &expr::Expr, name: &crate::scanner::Token) {
         for (depth, scope) in self.scopes.iter().rev().enumerate() {
             if scope.contains_key(&name.lexeme) {
@@ -288,6 +400,8 @@ impl Resolver {
     fn resolve_function(&mut self, function: &stmt::Function, ftype: FunctionType) {
         let enclosing_function = self.current_function;
         self.current_function = ftype;
+        // a loop around the declaration doesn't make break valid in the body
+        let enclosing_loops = std::mem::take(&mut self.loops);
 
         self.begin_scope();
         for param in &function.params {
@@ -297,5 +411,6 @@ impl Resolver {
         self.resolve_stmts(&function.body);
         self.end_scope();
         self.current_function = enclosing_function;
+        self.loops = enclosing_loops;
     }
 }
@@ -1,36 +1,54 @@
 use std::{collections::HashMap, fmt::Display, iter::Once};
 
 use crate::{
+    error::SyntaxError,
     interpreter::{Object, ObjectInner},
     null_obj, obj,
 };
 
 pub struct Scanner {
     source: String,
+    // Identifies `source` in the spans of the tokens
+    source_id: usize,
     tokens: Vec<Token>,
+    // Byte offsets into source, always on a char boundary
     start: usize,
     current: usize,
     line: usize,
+    // Column of `current` in chars, kept up to date as chars are consumed
+    current_column: usize,
+    start_line: usize,
+    start_column: usize,
     keywords: HashMap<&'static str, TokenType>,
-    pub had_error: bool,
+    // One entry per open "${", the span of its segment and the count of '{' opened inside it
+    interpolations: Vec<(Span, usize)>,
+    pub errors: Vec<SyntaxError>,
 }
 impl Scanner {
-    pub fn new(code: String) -> Self {
+    /// `source_id` ends up in every span, it tells which code a runtime error points into
+    pub fn new(code: String, source_id: usize) -> Self {
         Self {
             source: code,
+            source_id,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            current_column: 1,
+            start_line: 1,
+            start_column: 1,
             keywords: Self::keywords(),
-            had_error: false,
+            interpolations: Vec::new(),
+            errors: Vec::new(),
         }
     }
     fn keywords() -> HashMap<&'static str, TokenType> {
         use TokenType::*;
         vec![
             ("and", AND),
+            ("break", BREAK),
             ("class", CLASS),
+            ("continue", CONTINUE),
             ("else", ELSE),
             ("false", FALSE),
             ("for", FOR),
@@ -42,9 +60,11 @@ impl Scanner {
             ("return", RETURN),
             ("super", SUPER),
             ("this", THIS),
+            ("trait", TRAIT),
             ("true", TRUE),
             ("var", VAR),
             ("while", WHILE),
+            ("with", WITH),
         ]
         .into_iter()
         .collect()
@@ -52,12 +72,39 @@ impl Scanner {
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column();
             self.scan_token();
         }
+        for (span, _) in std::mem::take(&mut self.interpolations) {
+            self.errors
+                .push(SyntaxError::new(span, "Unterminated string interpolation."));
+        }
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column();
         self.tokens
-            .push(Token::new(TokenType::EOF, "".into(), self.line));
+            .push(Token::new(TokenType::EOF, "".into(), self.span()));
         self.tokens.clone()
     }
+    fn column(&self) -> usize {
+        self.current_column
+    }
+    /// Span from the start of the current token to the current position
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+            line: self.start_line,
+            column: self.start_column,
+            source: self.source_id,
+        }
+    }
+    /// Must be called right after consuming a '\n'
+    fn newline(&mut self) {
+        self.line += 1;
+        self.current_column = 1;
+    }
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
@@ -68,22 +115,54 @@ impl Scanner {
         match c {
             '(' => self.add_token(LEFT_PAREN),
             ')' => self.add_token(RIGHT_PAREN),
-            '{' => self.add_token(LEFT_BRACE),
-            '}' => self.add_token(RIGHT_BRACE),
+            '{' => {
+                if let Some((_, depth)) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(LEFT_BRACE)
+            }
+            // closes the innermost "${", the string continues after it
+            '}' if matches!(self.interpolations.last(), Some((_, 0))) => {
+                self.interpolations.pop();
+                self.string();
+            }
+            '}' => {
+                if let Some((_, depth)) = self.interpolations.last_mut() {
+                    *depth -= 1;
+                }
+                self.add_token(RIGHT_BRACE)
+            }
+            '[' => self.add_token(LEFT_BRACKET),
+            ']' => self.add_token(RIGHT_BRACKET),
             ',' => self.add_token(COMMA),
             '.' => self.add_token(DOT),
+            '-' if self.next_char_is('-') => self.add_token(MINUS_MINUS),
+            '-' if self.next_char_is('=') => self.add_token(MINUS_EQUAL),
             '-' => self.add_token(MINUS),
+            '+' if self.next_char_is('+') => self.add_token(PLUS_PLUS),
+            '+' if self.next_char_is('=') => self.add_token(PLUS_EQUAL),
             '+' => self.add_token(PLUS),
             ';' => self.add_token(SEMICOLON),
+            '*' if self.next_char_is('*') => self.add_token(STAR_STAR),
+            '*' if self.next_char_is('=') => self.add_token(STAR_EQUAL),
             '*' => self.add_token(STAR),
+            '%' => self.add_token(PERCENT),
+            '&' => self.add_token(AMPERSAND),
+            '|' => self.add_token(PIPE),
+            '^' => self.add_token(CARET),
+            '~' => self.add_token(TILDE),
+            '?' => self.add_token(QUESTION),
+            ':' => self.add_token(COLON),
 
             // 2char
             '!' if self.next_char_is('=') => self.add_token(BANG_EQUAL),
             '!' => self.add_token(BANG),
             '=' if self.next_char_is('=') => self.add_token(EQUAL_EQUAL),
             '=' => self.add_token(EQUAL),
+            '<' if self.next_char_is('<') => self.add_token(LESS_LESS),
             '<' if self.next_char_is('=') => self.add_token(LESS_EQUAL),
             '<' => self.add_token(LESS),
+            '>' if self.next_char_is('>') => self.add_token(GREATER_GREATER),
             '>' if self.next_char_is('=') => self.add_token(GREATER_EQUAL),
             '>' => self.add_token(GREATER),
             '/' if self.next_char_is('/') => {
@@ -92,13 +171,15 @@ impl Scanner {
                     let _ = self.advance();
                 }
             }
+            '/' if self.next_char_is('*') => self.block_comment(),
+            '/' if self.next_char_is('=') => self.add_token(SLASH_EQUAL),
             '/' => self.add_token(SLASH),
 
             // whitespace
             ' ' | '\r' | '\t' => (),
 
             // new line
-            '\n' => self.line += 1,
+            '\n' => self.newline(),
 
             // strings
             '"' => self.string(),
@@ -110,7 +191,7 @@ impl Scanner {
                 } else if c.is_lalpha() {
                     self.identifier();
                 } else {
-                    eprintln!("[line {}] Error: Unexpected character.", self.line);
+                    self.error("Unexpected character.");
                 }
             }
         }
@@ -127,75 +208,256 @@ impl Scanner {
         };
         self.add_token(ttype);
     }
+    /// `123`, `1_000.5`, `1e-9`, `0xff` or `0b1010`
     fn number(&mut self) {
-        while self.peek().is_ldigit() {
+        // rewind over the first digit, so the prefix can be checked
+        self.current = self.start;
+        self.current_column = self.start_column;
+        let radix = match (self.peek(), self.peek_next()) {
+            (Some('0'), Some('x' | 'X')) => 16,
+            (Some('0'), Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+        let mut valid = if radix == 10 {
+            let mut valid = self.digits(10);
+            if self.peek() == Some('.') && self.peek_next().is_ldigit() {
+                assert_eq!(self.advance(), '.');
+                valid &= self.digits(10);
+            }
+            if matches!(self.peek(), Some('e' | 'E')) {
+                self.advance();
+                if matches!(self.peek(), Some('+' | '-')) {
+                    self.advance();
+                }
+                valid &= self.digits(10);
+            }
+            valid
+        } else {
+            self.advance();
+            self.advance();
+            self.digits(radix)
+        };
+        // anything glued to the literal, like the 2 in 0b12
+        while self.peek().is_lalpha_numeric() {
             self.advance();
+            valid = false;
         }
-        if self.peek() == Some('.') && self.peek_next().is_ldigit() {
-            assert_eq!(self.advance(), '.');
+        if !valid {
+            self.error("Invalid number literal.");
+            return;
         }
-        while self.peek().is_ldigit() {
+
+        let text: String = self.source[self.start..self.current]
+            .chars()
+            .filter(|&c| c != '_')
+            .collect();
+        let value = if radix == 10 {
+            text.parse().unwrap()
+        } else {
+            text[2..].chars().fold(0., |value, c| {
+                value * radix as f64 + c.to_digit(radix).unwrap() as f64
+            })
+        };
+        self.add_token_with_literal(TokenType::NUMBER, obj!(value; ObjectInner::Number));
+    }
+    /// Consume digits with single `_` separators between them, false if the run is malformed
+    fn digits(&mut self, radix: u32) -> bool {
+        let mut valid = self.peek().is_some_and(|c| c.is_digit(radix));
+        let mut after_digit = false;
+        while let Some(c) = self.peek() {
+            if c.is_digit(radix) {
+                after_digit = true;
+            } else if c == '_' {
+                valid &= after_digit;
+                after_digit = false;
+            } else {
+                break;
+            }
             self.advance();
         }
-        self.add_token_with_literal(
-            TokenType::NUMBER,
-            obj!(self.source[self.start..self.current].parse().unwrap();ObjectInner::Number),
-        );
+        valid && after_digit
+    }
+    /// Skip a `/* */` comment, they nest
+    fn block_comment(&mut self) {
+        let opening = Span {
+            end: self.current,
+            ..self.span()
+        };
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek() {
+                None => {
+                    self.errors
+                        .push(SyntaxError::new(opening, "Unterminated block comment."));
+                    return;
+                }
+                Some('/') if self.peek_next() == Some('*') => {
+                    let _ = self.advance();
+                    let _ = self.advance();
+                    depth += 1;
+                }
+                Some('*') if self.peek_next() == Some('/') => {
+                    let _ = self.advance();
+                    let _ = self.advance();
+                    depth -= 1;
+                }
+                Some(_) => {
+                    if self.advance() == '\n' {
+                        self.newline();
+                    }
+                }
+            }
+        }
     }
+    /// Scan a string segment, ending at the closing quote (STRING) or at a "${" (INTERPOLATION)
     fn string(&mut self) {
+        let mut value = String::new();
         while self.peek() != Some('"') && !self.is_at_end() {
-            if self.peek() == Some('\n') {
-                self.line += 1;
+            if self.peek() == Some('$') && self.peek_next() == Some('{') {
+                let _ = self.advance();
+                let _ = self.advance();
+                self.add_token_with_literal(
+                    TokenType::INTERPOLATION,
+                    obj!(value; ObjectInner::String),
+                );
+                self.interpolations.push((self.span(), 0));
+                return;
+            }
+            match self.advance() {
+                '\n' => {
+                    self.newline();
+                    value.push('\n');
+                }
+                '\\' => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                c => value.push(c),
             }
-            self.advance();
         }
         if self.is_at_end() {
-            eprintln!("[line {}] Error: Unterminated string.", self.line);
-            self.had_error = true;
+            self.error("Unterminated string.");
             return;
         }
 
         assert_eq!(self.advance(), '"');
 
-        let value = self.source[self.start + 1..self.current - 1].to_string();
         self.add_token_with_literal(TokenType::STRING, obj!(value; ObjectInner::String));
     }
-    fn peek(&self) -> Option<char> {
-        if self.is_at_end() {
-            return None;
+    /// Decode the escape sequence following a '\\', `None` if it is invalid
+    fn escape(&mut self) -> Option<char> {
+        let start = self.current - 1;
+        let column = self.column() - 1;
+        let c = match self.peek() {
+            // leave newlines to the string loop so lines are still counted
+            Some(c) if c != '\n' => self.advance(),
+            _ => {
+                self.error_at(start, column, "Invalid escape sequence.");
+                return None;
+            }
+        };
+        match c {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '0' => Some('\0'),
+            '$' => Some('$'),
+            'u' => {
+                let c = self.unicode_escape();
+                if c.is_none() {
+                    self.error_at(start, column, "Invalid unicode escape, expected \\u{XXXX}.");
+                }
+                c
+            }
+            _ => {
+                self.error_at(start, column, "Invalid escape sequence.");
+                None
+            }
         }
-        Some(self.source.as_bytes()[self.current] as char)
     }
-    fn peek_next(&self) -> Option<char> {
-        if self.current + 1 >= self.source.len() {
+    /// The `{XXXX}` part of a `\\u{XXXX}` escape, up to 6 hex digits naming a unicode scalar value
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.next_char_is('{') {
             return None;
         }
-        Some(self.source.as_bytes()[self.current + 1] as char)
+        let digits_start = self.current;
+        while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+            let _ = self.advance();
+        }
+        let digits_end = self.current;
+        if digits_end == digits_start || digits_end - digits_start > 6 || !self.next_char_is('}') {
+            return None;
+        }
+        u32::from_str_radix(&self.source[digits_start..digits_end], 16)
+            .ok()
+            .and_then(char::from_u32)
+    }
+    fn error(&mut self, message: &str) {
+        self.errors.push(SyntaxError::new(self.span(), message));
+    }
+    /// Report an error spanning from `start` on the current line to the current position
+    fn error_at(&mut self, start: usize, column: usize, message: &str) {
+        let span = Span {
+            start,
+            end: self.current,
+            line: self.line,
+            column,
+            source: self.source_id,
+        };
+        self.errors.push(SyntaxError::new(span, message));
+    }
+    fn peek(&self) -> Option<char> {
+        self.source[self.current..].chars().next()
+    }
+    fn peek_next(&self) -> Option<char> {
+        self.source[self.current..].chars().nth(1)
     }
     fn next_char_is(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        if self.source.as_bytes()[self.current] as char != expected {
+        if self.peek() != Some(expected) {
             return false;
         }
 
-        self.current += 1;
+        self.current += expected.len_utf8();
+        self.current_column += 1;
         true
     }
     fn advance(&mut self) -> char {
-        let cchar = self.source.as_bytes()[self.current] as char;
-        self.current += 1;
+        let cchar = self.peek().unwrap();
+        self.current += cchar.len_utf8();
+        self.current_column += 1;
         cchar
     }
     fn add_token(&mut self, ttype: TokenType) {
         let text = self.source[self.start..self.current].to_string();
-        self.tokens.push(Token::new(ttype, text, self.line));
+        self.tokens.push(Token::new(ttype, text, self.span()));
     }
     fn add_token_with_literal(&mut self, ttype: TokenType, literal: Object) {
         let text = self.source[self.start..self.current].to_string();
         self.tokens
-            .push(Token::new_with_literal(ttype, text, self.line, literal));
+            .push(Token::new_with_literal(ttype, text, self.span(), literal));
+    }
+}
+
+/// A region of the source, `line` and `column` (in chars) locate `start` and are 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    /// The code the span points into, counting the runs of a `Lox` from 0
+    pub source: usize,
+}
+impl Span {
+    /// Span covering from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
     }
 }
 
@@ -205,27 +467,36 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Object,
     pub line: usize,
+    pub column: usize,
+    /// Byte offsets of the lexeme in the source
+    pub start: usize,
+    pub end: usize,
+    /// See `Span::source`
+    pub source: usize,
 }
 impl Token {
-    pub fn new_with_literal(
-        ttype: TokenType,
-        lexeme: String,
-        line: usize,
-        literal: Object,
-    ) -> Self {
+    pub fn new_with_literal(ttype: TokenType, lexeme: String, span: Span, literal: Object) -> Self {
         Self {
             ttype,
             lexeme,
-            line,
+            line: span.line,
+            column: span.column,
+            start: span.start,
+            end: span.end,
+            source: span.source,
             literal,
         }
     }
-    pub fn new(ttype: TokenType, lexeme: String, line: usize) -> Self {
-        Self {
-            ttype,
-            lexeme,
-            line,
-            literal: null_obj!(),
+    pub fn new(ttype: TokenType, lexeme: String, span: Span) -> Self {
+        Self::new_with_literal(ttype, lexeme, span, null_obj!())
+    }
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.end,
+            line: self.line,
+            column: self.column,
+            source: self.source,
         }
     }
 }
@@ -244,6 +515,8 @@ pub enum TokenType {
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
     COMMA,
     DOT,
     MINUS,
@@ -251,6 +524,13 @@ pub enum TokenType {
     SEMICOLON,
     SLASH,
     STAR,
+    PERCENT,
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
+    QUESTION,
+    COLON,
 
     // One or two character tokens.
     BANG,
@@ -261,15 +541,28 @@ pub enum TokenType {
     GREATER_EQUAL,
     LESS,
     LESS_EQUAL,
+    PLUS_EQUAL,
+    MINUS_EQUAL,
+    STAR_EQUAL,
+    SLASH_EQUAL,
+    PLUS_PLUS,
+    MINUS_MINUS,
+    STAR_STAR,
+    LESS_LESS,
+    GREATER_GREATER,
 
     // Literals.
     IDENTIFIER,
     STRING,
+    /// A string segment followed by "${", the embedded expression comes next
+    INTERPOLATION,
     NUMBER,
 
     // Keywords.
     AND,
+    BREAK,
     CLASS,
+    CONTINUE,
     ELSE,
     FALSE,
     FUN,
@@ -281,9 +574,11 @@ pub enum TokenType {
     RETURN,
     SUPER,
     THIS,
+    TRAIT,
     TRUE,
     VAR,
     WHILE,
+    WITH,
 
     EOF,
 }
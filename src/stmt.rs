@@ -7,11 +7,13 @@ ast!(
 
 Block => visit_block_stmt => statements Vec<Stmt>,
 
-Class => visit_class_stmt => name Token superclass Option<expr::Variable> methods Vec<Function>,
+Class => visit_class_stmt => name Token superclass Option<expr::Variable> traits Vec<expr::Variable> methods Vec<Function> class_methods Vec<Function>,
+
+Trait => visit_trait_stmt => name Token methods Vec<Function>,
 
 Expression => visit_expression_stmt => expression Expr,
 
-Function => visit_function_stmt => name Token params Vec<Token> body Vec<Stmt>,
+Function => visit_function_stmt => name Token params Vec<Token> body Vec<Stmt> getter bool,
 
 If => visit_if_stmt => condition Expr then_branch Box<Stmt> else_branch Option<Box<Stmt>>,
 
@@ -21,5 +23,9 @@ Var => visit_var_stmt => name Token initializer Option<Expr>,
 
 Return => visit_return_stmt => keyword Token value Option<Expr>,
 
-While => visit_while_stmt => condition Expr body Box<Stmt>,
+While => visit_while_stmt => condition Expr body Box<Stmt> increment Option<Box<Expr>>,
+
+Break => visit_break_stmt => keyword Token,
+
+Continue => visit_continue_stmt => keyword Token,
 );
@@ -1,4 +1,7 @@
-use jlox::{Lox, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use jlox::{Abort, Frame, FromLox, FromLoxError, IntoLox, Lox, LoxError, Object, Result, Span};
 
 macro_rules! assert_test_eq {
     ($name: literal => $expected: literal) => {
@@ -13,16 +16,35 @@ macro_rules! test_lox_programs  {
         #[test]
         fn $name() -> Result<()> {
             let mut lox = Lox::default();
-            lox.run_file(format!("lox_files/{}.lox", stringify!($name)))
+            lox.run_file(format!("lox_files/{}.lox", stringify!($name)))?;
+            Ok(())
         }
         )+
     }
 }
 
-test_lox_programs!(hello env fib fun hidden_var fact closure_scope class instance run_class_method class_cake init inherit ssuper);
+test_lox_programs!(hello env fib fun hidden_var fact closure_scope class instance run_class_method class_cake init inherit ssuper unicode escapes interpolation block_comment numbers break_continue lambda conditional compound operators list list_cycle map map_cycle deep_nesting class_methods getters traits);
 
 #[test]
 fn test_lox_programs() -> Result<()> {
+    assert_test_eq!("traits" => "true\ntrue\nmoney(5)\nPrintable\nboth\n");
+    assert_test_eq!("getters" => "12\n20\n5x4 with area 20\n21\nsquare 4\n3\n");
+    assert_test_eq!("class_methods" => "9\n8\n0\nStopwatch, a Counter\nStopwatch instance\n25\n");
+    assert_test_eq!("deep_nesting" => "[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[...]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]\n{a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {a: {...}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}}\ndropped\n");
+    assert_test_eq!("map_cycle" => "{a: 1, self: {...}}\n{a: 1, self: {...}, xs: [{...}]}\n[{a: 1, self: {...}, xs: [...]}]\n");
+    assert_test_eq!("map" => "{ada: 36, alan: 41}\n36\n3\ntrue\nfalse\n[ada, alan, grace]\n[37, 41, 85]\n41\n{ada: 37, grace: 85}\n{}\none yes nothing\nzero\n2\n{xs: [1, 2, 3], inner: {k: v}}\nv\nterpolated\n");
+    assert_test_eq!("list_cycle" => "[1, [...]]\n[[1, [...]], [1, [...]]]\n[[...], [...]]\n");
+    assert_test_eq!("list" => "[1, 2, 3]\n4\n[1, two, 3]\n4\n4\n[0, 1, two, 3]\n1\n[two, 3]\n[]\n[0, two, 3, shared]\ntrue\nfalse\n13\nfirst 1\n");
+    assert_test_eq!("operators" => "1\n-1\n1.5\n1024\n512\n-4\n0.5\n2\n7\n5\n-6\n16\n-4\ntrue\n6\n2\n");
+    assert_test_eq!("compound" => "3\n2\n10\n2.5\nabcd\n0\n1\n2\n2\n0\n5\n5\n7\n2\n9\n6\n0\n1\n2\n");
+    assert_test_eq!("conditional" => "positive\nsmall\nfalse\n12\nevaluated then\nthen\none\n2\n");
+    assert_test_eq!("lambda" => "42\n3\n<fn lambda>\n2\nimmediately\n");
+    assert_test_eq!("break_continue" => "0\n1\n3\n4\n4\n0\n10\n20\n8\nouter\n");
+    assert_test_eq!("numbers" => "123\n123.45\n1000000\n255\n65535\n10\n240\n1000\n0.025\n100000000000\n28\n");
+    assert_test_eq!("block_comment" => "1\n2\n3\n4\n");
+    assert_test_eq!("interpolation" => "total: 3\n12\nhi lox!\nnested inner 1 and ${literal}\n(3, 4.5) Point instance nil true\nmulti\nline 1\n");
+    assert_test_eq!("escapes" => "a\tb\nline\nbreak\nsay \"hi\"\nback\\slash\nHé🦀\ntrue\n");
+    assert_test_eq!("unicode" => "crème brûlée\nemoji: 🦀🚀\nこんにちは 世界\n");
     assert_test_eq!("ssuper" => "Fry until golden brown.\nPipe full of custard and coat with chocolate.\n");
     assert_test_eq!("inherit" => "Fry until golden brown.\n");
     assert_test_eq!("init" => "Foo instance\n");
@@ -96,19 +118,541 @@ global c
     Ok(())
 }
 
+#[test]
+fn test_lox_errors() {
+    let mut lox = Lox::default();
+    assert!(matches!(lox.run("var a = \"oops;"), Err(LoxError::Scan(_))));
+    assert!(matches!(lox.run("print 1 € 2;"), Err(LoxError::Scan(_))));
+    assert!(matches!(lox.run("print \"a${1;"), Err(LoxError::Scan(_))));
+    for number in ["0x", "0b102", "1__0", "1_", "1e", "1e+", "0xg", "12abc"] {
+        assert!(
+            matches!(
+                lox.run(&format!("print {};", number)),
+                Err(LoxError::Scan(_))
+            ),
+            "{}",
+            number
+        );
+    }
+    assert!(matches!(lox.run("print 1 +;"), Err(LoxError::Parse(_))));
+    assert!(matches!(
+        lox.run("print true ? 1;"),
+        Err(LoxError::Parse(_))
+    ));
+    assert!(matches!(lox.run("1 += 2;"), Err(LoxError::Parse(_))));
+    assert!(matches!(lox.run("++nil;"), Err(LoxError::Parse(_))));
+    assert!(matches!(
+        lox.run("print \"${1 2}\";"),
+        Err(LoxError::Parse(_))
+    ));
+    assert!(matches!(
+        lox.run_file("lox_files/super_with_no_superclass.lox"),
+        Err(LoxError::Resolve(_))
+    ));
+    assert!(matches!(lox.run("break;"), Err(LoxError::Resolve(_))));
+    assert!(matches!(
+        lox.run("trait T { m() { super.m(); } }"),
+        Err(LoxError::Resolve(_))
+    ));
+    assert!(matches!(
+        lox.run("trait T {} class A with T, T {}"),
+        Err(LoxError::Resolve(_))
+    ));
+    assert!(matches!(
+        lox.run("while (true) { fun f() { continue; } }"),
+        Err(LoxError::Resolve(_))
+    ));
+    match lox.run("var a = 1;\nprint -\"a\";") {
+        Err(LoxError::Runtime(e)) => {
+            assert_eq!(e.message(), "Operand must be a number.");
+            assert_eq!(e.line(), 2);
+        }
+        _ => panic!("expected a runtime error"),
+    }
+    for (code, message) in [
+        ("print 1.5 & 1;", "Operands must be integers."),
+        ("print ~0.5;", "Operand must be an integer."),
+        ("print ~\"a\";", "Operand must be a number."),
+        ("print 1 << 64;", "Shift amount must be between 0 and 63."),
+        (
+            "print [1, 2][2];",
+            "List index 2 out of bounds for length 2.",
+        ),
+        ("[1][-1] = 0;", "List index -1 out of bounds for length 1."),
+        (
+            "print [1][0.5];",
+            "List index must be an integer, found 0.5.",
+        ),
+        (
+            "print [1][\"0\"];",
+            "List index must be an integer, found string.",
+        ),
+        ("print nil[0];", "Only lists and maps can be indexed."),
+        ("print {\"a\": 1}[\"b\"];", "Undefined key 'b'."),
+        (
+            "print {[]: 1};",
+            "Map key must be a string, number, bool or nil, found list.",
+        ),
+        (
+            "var m = {}; m[m] = 1;",
+            "Map key must be a string, number, bool or nil, found map.",
+        ),
+        ("({}).remove(1);", "Undefined key '1'."),
+        ("[].pop();", "Can't pop from an empty list."),
+        (
+            "[1].insert(2, 0);",
+            "List index 2 out of bounds for length 1.",
+        ),
+        ("[].first;", "Undefined property 'first'."),
+        ("class A { m() {} } A.m();", "Undefined property 'm'."),
+        (
+            "class B {} class C with B {}",
+            "Only traits can be used with 'with'.",
+        ),
+        (
+            "trait X { m() {} } trait Y { m() {} } class D with X, Y {}",
+            "Method 'm' is defined by both 'X' and 'Y'.",
+        ),
+    ] {
+        match lox.run(code) {
+            Err(LoxError::Runtime(e)) => assert_eq!(e.message(), message),
+            _ => panic!("expected a runtime error for {}", code),
+        }
+    }
+    // the interpreter is still usable after an error
+    assert!(lox.run("print a;").is_ok());
+}
+
+#[test]
+fn test_error_spans() {
+    let mut lox = Lox::new(
+        Arc::new(Mutex::new(Vec::new())),
+        Arc::new(Mutex::new(Vec::new())),
+    );
+    match lox.run("print 1;\nprint 1 +;") {
+        Err(LoxError::Parse(errors)) => assert_eq!(
+            errors[0].span,
+            Span {
+                start: 18,
+                end: 19,
+                line: 2,
+                column: 10,
+                source: 0
+            }
+        ),
+        _ => panic!("expected a parse error"),
+    }
+    // lines inside block comments are counted, unterminated ones point at the opening
+    match lox.run("/* a\n b */ print -nil;\n  /* c /* d */\n") {
+        Err(LoxError::Scan(errors)) => assert_eq!(
+            errors[0].span,
+            Span {
+                start: 25,
+                end: 27,
+                line: 3,
+                column: 3,
+                source: 1
+            }
+        ),
+        _ => panic!("expected a scan error"),
+    }
+    match lox.run("/* a\n b */ print -nil;") {
+        Err(LoxError::Runtime(e)) => assert_eq!(e.line(), 2),
+        _ => panic!("expected a runtime error"),
+    }
+    // escape errors point at the backslash
+    match lox.run("print \"a\\qb \\u{110000}\";") {
+        Err(LoxError::Scan(errors)) => {
+            let spans: Vec<_> = errors.iter().map(|e| (e.span.column, e.span.end)).collect();
+            assert_eq!(spans, [(9, 10), (13, 22)]);
+            assert_eq!(errors[0].message, "Invalid escape sequence.");
+        }
+        _ => panic!("expected a scan error"),
+    }
+    // columns count chars, offsets count bytes
+    match lox.run("var é = -nil;") {
+        Err(LoxError::Runtime(e)) => assert_eq!(
+            e.span(),
+            Span {
+                start: 9,
+                end: 10,
+                line: 1,
+                column: 9,
+                source: 4
+            }
+        ),
+        _ => panic!("expected a runtime error"),
+    }
+    // a long minified line scans in linear time
+    let code = format!("{}print 0x1 + -nil;", " ".repeat(200_000));
+    match lox.run(&code) {
+        Err(LoxError::Runtime(e)) => assert_eq!(e.span().column, 200_013),
+        _ => panic!("expected a runtime error"),
+    }
+}
+
+#[test]
+fn test_lox_diagnostics() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let diagnostics = Arc::new(Mutex::new(Vec::new()));
+    let mut lox = Lox::new(output.clone(), diagnostics.clone());
+    let _ = lox.run("print 1;\nprint 1 +;");
+    let _ = lox.run("print -nil;");
+    assert!(output.lock().unwrap().is_empty());
+    assert_eq!(
+        String::from_utf8(diagnostics.lock().unwrap().clone()).unwrap(),
+        "\
+error: Expect expression.
+ --> line 2:10
+  |
+2 | print 1 +;
+  |          ^ at ';'
+error: Operand must be a number.
+ --> line 1:7
+  |
+1 | print -nil;
+  |       ^
+  = note: found nil
+"
+    );
+
+    // the error is in code from an earlier run than the one that failed
+    diagnostics.lock().unwrap().clear();
+    lox.run("fun f() { return -nil; }").unwrap();
+    let _ = lox.run("print \"padding padding\"; f();");
+    assert_eq!(
+        String::from_utf8(diagnostics.lock().unwrap().clone()).unwrap(),
+        "\
+error: Operand must be a number.
+ --> line 1:18
+  |
+1 | fun f() { return -nil; }
+  |                  ^
+  = note: found nil
+stack traceback:
+  f() called at line 1
+"
+    );
+
+    diagnostics.lock().unwrap().clear();
+    lox.set_color(true);
+    let _ = lox.run("print 1 +;");
+    let rendered = String::from_utf8(diagnostics.lock().unwrap().clone()).unwrap();
+    assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m"));
+}
+
+#[test]
+fn test_stack_trace() {
+    let diagnostics = Arc::new(Mutex::new(Vec::new()));
+    let mut lox = Lox::new(Arc::new(Mutex::new(Vec::new())), diagnostics.clone());
+    let code = "class Greeter {
+  greet(name) {
+    return \"hi \" + name;
+  }
+}
+fun outer() {
+  return Greeter().greet(nil);
+}
+outer();";
+    match lox.run(code) {
+        Err(LoxError::Runtime(e)) => assert_eq!(
+            e.trace(),
+            [
+                Frame {
+                    function: "Greeter.greet".into(),
+                    line: Some(7)
+                },
+                Frame {
+                    function: "outer".into(),
+                    line: Some(9)
+                }
+            ]
+        ),
+        _ => panic!("expected a runtime error"),
+    }
+    let rendered = String::from_utf8(diagnostics.lock().unwrap().clone()).unwrap();
+    assert!(rendered.ends_with(
+        "stack traceback:\n  Greeter.greet() called at line 7\n  outer() called at line 9\n"
+    ));
+
+    // the function the host called is the outermost frame
+    lox.run("fun inner() { return -nil; }\nfun handler() { inner(); }")
+        .unwrap();
+    match lox.call("handler", vec![]) {
+        Err(LoxError::Runtime(e)) => assert_eq!(
+            e.trace(),
+            [
+                Frame {
+                    function: "inner".into(),
+                    line: Some(2)
+                },
+                Frame {
+                    function: "handler".into(),
+                    line: None
+                }
+            ]
+        ),
+        _ => panic!("expected a runtime error"),
+    }
+    let rendered = String::from_utf8(diagnostics.lock().unwrap().clone()).unwrap();
+    assert!(rendered.ends_with("  inner() called at line 2\n  handler() called by the host\n"));
+
+    // frames of a failed run don't leak into the next one
+    match lox.run("print -nil;") {
+        Err(LoxError::Runtime(e)) => assert!(e.trace().is_empty()),
+        _ => panic!("expected a runtime error"),
+    }
+}
+
+#[test]
+fn test_native_functions() -> Result<()> {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut lox = Lox::new(output.clone(), Arc::new(Mutex::new(std::io::sink())));
+    lox.define_native("hypot", 2, |arguments| {
+        let [a, b] = [&arguments[0], &arguments[1]].map(|arg| f64::from_lox(arg.clone()));
+        match (a, b) {
+            (Ok(a), Ok(b)) => Ok(a.hypot(b).into_lox()),
+            _ => Err("Arguments must be numbers.".into()),
+        }
+    });
+    lox.run("print hypot(3, 4);")?;
+    assert_eq!(output.lock().unwrap().as_slice(), b"5\n");
+
+    match lox.run("\nhypot(3, \"4\");") {
+        Err(LoxError::Runtime(e)) => {
+            assert_eq!(e.message(), "Arguments must be numbers.");
+            assert_eq!(e.line(), 2);
+        }
+        _ => panic!("expected a runtime error"),
+    }
+    assert!(matches!(lox.run("hypot(3);"), Err(LoxError::Runtime(_))));
+    Ok(())
+}
+
+#[test]
+fn test_value_conversions() -> Result<()> {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut lox = Lox::new(output.clone(), Arc::new(Mutex::new(std::io::sink())));
+    lox.define_native("greet", 1, |arguments| {
+        let name = Option::<String>::from_lox(arguments[0].clone())?;
+        Ok(format!("Hello, {}!", name.as_deref().unwrap_or("stranger")).into_lox())
+    });
+    lox.run("print greet(\"Lox\");\nprint greet(nil);")?;
+    assert_eq!(
+        output.lock().unwrap().as_slice(),
+        b"Hello, Lox!\nHello, stranger!\n"
+    );
+    match lox.run("greet(1);") {
+        Err(LoxError::Runtime(e)) => assert_eq!(e.message(), "Expected string but got number."),
+        _ => panic!("expected a runtime error"),
+    }
+
+    assert_eq!(f64::from_lox(2.5.into_lox()), Ok(2.5));
+    assert_eq!(bool::from_lox(true.into_lox()), Ok(true));
+    assert_eq!(String::from_lox("lox".into_lox()), Ok("lox".to_string()));
+    assert_eq!(<()>::from_lox(().into_lox()), Ok(()));
+    assert_eq!(Option::<f64>::from_lox(None::<f64>.into_lox()), Ok(None));
+    assert_eq!(
+        Vec::<f64>::from_lox(vec![1.0, 2.0].into_lox()),
+        Ok(vec![1.0, 2.0])
+    );
+    assert_eq!(
+        Vec::<f64>::from_lox(vec![true].into_lox()),
+        Err(FromLoxError {
+            expected: "number",
+            found: "bool"
+        })
+    );
+    let ages = HashMap::from([("ada".to_string(), 36.0), ("alan".to_string(), 41.0)]);
+    assert_eq!(
+        HashMap::<String, f64>::from_lox(ages.clone().into_lox()),
+        Ok(ages)
+    );
+    let flags = HashMap::from([(true, "yes".to_string())]);
+    assert_eq!(
+        HashMap::<bool, String>::from_lox(flags.clone().into_lox()),
+        Ok(flags)
+    );
+    lox.run("var numbered = {1: 2};")?;
+    assert_eq!(
+        HashMap::<String, f64>::from_lox(lox.get_global("numbered").unwrap()),
+        Err(FromLoxError {
+            expected: "string",
+            found: "number"
+        })
+    );
+    assert_eq!(
+        HashMap::<String, f64>::from_lox(vec![1.0].into_lox()),
+        Err(FromLoxError {
+            expected: "map",
+            found: "list"
+        })
+    );
+    lox.run("var xs = []; xs.push(xs);")?;
+    assert_eq!(
+        format!("{:?}", lox.get_global("xs").unwrap()),
+        "List [[...]]"
+    );
+    assert_eq!(
+        bool::from_lox(Object::default()),
+        Err(FromLoxError {
+            expected: "bool",
+            found: "nil"
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_host_calls() -> Result<()> {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut lox = Lox::new(output.clone(), Arc::new(Mutex::new(std::io::sink())));
+    lox.set_global("greeting", "Hello");
+    lox.run(
+        "\
+fun on_message(name) { return greeting + \", \" + name; }
+class Counter { init(start) { this.count = start; } }
+var total = 0;
+fun add(n) { total = total + n; }",
+    )?;
+
+    let reply = lox.call("on_message", vec!["host".into_lox()])?;
+    assert_eq!(String::from_lox(reply)?, "Hello, host");
+    lox.call("add", vec![2.0.into_lox()])?;
+    lox.call("add", vec![3.0.into_lox()])?;
+    assert_eq!(f64::from_lox(lox.get_global("total").unwrap())?, 5.0);
+
+    let counter = lox.call("Counter", vec![7.0.into_lox()])?;
+    assert_eq!(counter.to_string(), "Counter instance");
+
+    assert!(lox.get_global("missing").is_none());
+    assert!(matches!(
+        lox.call("missing", vec![]),
+        Err(LoxError::Call(_))
+    ));
+    assert!(matches!(lox.call("total", vec![]), Err(LoxError::Call(_))));
+    match lox.call("add", vec![]) {
+        Err(LoxError::Call(message)) => assert_eq!(message, "Expected 1 arguments but got 0."),
+        _ => panic!("expected an arity error"),
+    }
+    assert!(matches!(
+        lox.call("add", vec!["1".into_lox()]),
+        Err(LoxError::Runtime(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_step_budget() -> Result<()> {
+    let mut lox = Lox::new(
+        Arc::new(Mutex::new(std::io::sink())),
+        Arc::new(Mutex::new(std::io::sink())),
+    );
+    lox.set_step_budget(Some(1_000));
+    assert!(matches!(
+        lox.run("while (true) {}"),
+        Err(LoxError::Aborted(Abort::BudgetExhausted))
+    ));
+    lox.set_step_budget(Some(100));
+    assert!(matches!(
+        lox.run("fun forever() { forever(); }\nforever();"),
+        Err(LoxError::Aborted(Abort::BudgetExhausted))
+    ));
+    // the budget applies to every run separately
+    lox.set_step_budget(Some(1_000));
+    lox.run("for (var i = 0; i < 100; i = i + 1) {}")?;
+    lox.run("for (var i = 0; i < 100; i = i + 1) {}")?;
+    lox.set_step_budget(None);
+    lox.run("for (var i = 0; i < 2000; i = i + 1) {}")?;
+    Ok(())
+}
+
+#[test]
+fn test_interrupt() -> Result<()> {
+    let mut lox = Lox::new(
+        Arc::new(Mutex::new(std::io::sink())),
+        Arc::new(Mutex::new(std::io::sink())),
+    );
+    let handle = lox.interrupt_handle();
+    let interrupter = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        handle.interrupt();
+    });
+    assert!(matches!(
+        lox.run("class Spin { init() { while (true) {} } }\nSpin();"),
+        Err(LoxError::Aborted(Abort::Interrupted))
+    ));
+    interrupter.join().unwrap();
+    // the interrupt is consumed
+    lox.run("print 1;")?;
+    Ok(())
+}
+
+#[test]
+fn test_call_depth() -> Result<()> {
+    let mut lox = Lox::new(
+        Arc::new(Mutex::new(std::io::sink())),
+        Arc::new(Mutex::new(std::io::sink())),
+    );
+    lox.run("fun depth(n) { if (n == 0) return 0; return depth(n - 1) + 1; }")?;
+    lox.set_max_call_depth(64);
+    match lox.run("\ndepth(100);") {
+        Err(LoxError::Runtime(e)) => {
+            assert_eq!(e.message(), "Stack overflow.");
+            // raised by the innermost call
+            assert_eq!(e.line(), 1);
+        }
+        _ => panic!("expected a stack overflow"),
+    }
+    lox.run("depth(63);")?;
+
+    lox.set_max_call_depth(5_000);
+    let depth = lox.with_stack_size(256 * 1024 * 1024, |lox| {
+        lox.call("depth", vec![4_000.0.into_lox()])
+    })??;
+    assert_eq!(f64::from_lox(depth)?, 4_000.0);
+    Ok(())
+}
+
+#[test]
+fn test_default_call_depth() -> Result<()> {
+    let overflows = |lox: &mut Lox| {
+        for code in [
+            "fun f() { f(); } f();",
+            "fun g() { while (true) { { var x = [g()]; } } } g();",
+        ] {
+            match lox.run(code) {
+                Err(LoxError::Runtime(e)) => assert_eq!(e.message(), "Stack overflow."),
+                _ => panic!("expected a stack overflow"),
+            }
+        }
+    };
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut lox = Lox::new(output.clone(), Arc::new(Mutex::new(std::io::sink())));
+    // the default fits the 8 MiB the cli runs with
+    lox.with_stack_size(8 * 1024 * 1024, |lox| {
+        lox.run("fun sum(n) { if (n == 0) return 0; return n + sum(n - 1); }\nprint sum(200);")?;
+        overflows(lox);
+        Ok::<_, LoxError>(())
+    })??;
+    assert_eq!(output.lock().unwrap().as_slice(), b"20100\n");
+
+    // the depth documented for a spawned thread's default 2 MiB
+    std::thread::spawn(move || {
+        lox.set_max_call_depth(64);
+        overflows(&mut lox);
+    })
+    .join()
+    .unwrap();
+    Ok(())
+}
+
 // helpers
 
 fn run_test_with_output(name: &str) -> Result<String> {
-    let out = std::process::Command::new("cargo")
-        .args(&["t", "-q", "--", "--exact", name, "--nocapture"])
-        .output()?;
-    Ok(parse_success(String::from_utf8(out.stdout)?))
-}
-
-fn parse_success(stdout: String) -> String {
-    const START: &str = "running 1 test\n";
-    const END: &str = ".\ntest result";
-    let start_idx = stdout.find(START).unwrap() + START.len();
-    let end_idx = stdout.find(END).unwrap();
-    stdout[start_idx..end_idx].to_owned()
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut lox = Lox::new(output.clone(), Arc::new(Mutex::new(std::io::sink())));
+    lox.run_file(format!("lox_files/{}.lox", name))?;
+    let output = output.lock().unwrap().clone();
+    Ok(String::from_utf8(output)?)
 }